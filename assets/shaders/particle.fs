@@ -0,0 +1,12 @@
+#version 330 core
+
+in vec2 TexCoords;
+in vec4 ParticleColour;
+out vec4 target;
+
+uniform sampler2D sprite;
+
+void main()
+{
+    target = texture(sprite, TexCoords) * ParticleColour;
+}
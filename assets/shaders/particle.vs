@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 position;
+in vec2 texCoords;
+
+out vec2 TexCoords;
+out vec4 ParticleColour;
+
+uniform Locals {
+    vec4 particleColour;
+    vec2 offset;
+    float scale;
+};
+uniform mat4 projection;
+
+void main()
+{
+    TexCoords = texCoords;
+    ParticleColour = particleColour;
+    gl_Position = projection * vec4((position * scale) + offset, 0.0, 1.0);
+}
@@ -0,0 +1,66 @@
+#version 330 core
+
+in vec2 TexCoords;
+out vec4 target;
+
+uniform sampler2D scene;
+
+uniform int chaos;
+uniform int confuse;
+uniform int shake;
+
+const float offset = 1.0 / 300.0;
+const vec2 offsets[9] = vec2[](
+    vec2(-offset,  offset),  // top-left
+    vec2( 0.0,     offset),  // top-center
+    vec2( offset,  offset),  // top-right
+    vec2(-offset,  0.0),     // center-left
+    vec2( 0.0,     0.0),     // center-center
+    vec2( offset,  0.0),     // center-right
+    vec2(-offset, -offset),  // bottom-left
+    vec2( 0.0,    -offset),  // bottom-center
+    vec2( offset, -offset)   // bottom-right
+);
+const float edge_kernel[9] = float[](
+    -1, -1, -1,
+    -1,  8, -1,
+    -1, -1, -1
+);
+const float blur_kernel[9] = float[](
+    1.0 / 16, 2.0 / 16, 1.0 / 16,
+    2.0 / 16, 4.0 / 16, 2.0 / 16,
+    1.0 / 16, 2.0 / 16, 1.0 / 16
+);
+
+void main()
+{
+    vec3 samples[9];
+    if (chaos != 0 || shake != 0)
+    {
+        for (int i = 0; i < 9; i++)
+            samples[i] = vec3(texture(scene, TexCoords.st + offsets[i]));
+    }
+
+    if (chaos != 0)
+    {
+        vec3 colour = vec3(0.0);
+        for (int i = 0; i < 9; i++)
+            colour += samples[i] * edge_kernel[i];
+        target = vec4(colour, 1.0);
+    }
+    else if (confuse != 0)
+    {
+        target = vec4(1.0 - texture(scene, TexCoords).rgb, 1.0);
+    }
+    else if (shake != 0)
+    {
+        vec3 colour = vec3(0.0);
+        for (int i = 0; i < 9; i++)
+            colour += samples[i] * blur_kernel[i];
+        target = vec4(colour, 1.0);
+    }
+    else
+    {
+        target = texture(scene, TexCoords);
+    }
+}
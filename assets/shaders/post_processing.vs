@@ -0,0 +1,36 @@
+#version 330 core
+
+in vec2 position;
+in vec2 texCoords;
+
+out vec2 TexCoords;
+
+uniform int chaos;
+uniform int confuse;
+uniform int shake;
+uniform float time;
+
+void main()
+{
+    gl_Position = vec4(position, 0.0, 1.0);
+    if (chaos != 0)
+    {
+        float strength = 0.3;
+        TexCoords = vec2(texCoords.x + sin(time) * strength,
+                         texCoords.y + cos(time) * strength);
+    }
+    else if (confuse != 0)
+    {
+        TexCoords = vec2(1.0 - texCoords.x, 1.0 - texCoords.y);
+    }
+    else
+    {
+        TexCoords = texCoords;
+    }
+    if (shake != 0)
+    {
+        float strength = 0.01;
+        gl_Position.x += cos(time * 10) * strength;
+        gl_Position.y += cos(time * 15) * strength;
+    }
+}
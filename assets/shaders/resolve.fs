@@ -0,0 +1,16 @@
+#version 330 core
+
+out vec4 target;
+
+uniform sampler2DMS scene;
+uniform int samples;
+
+// Average all of the samples for this pixel.
+void main()
+{
+    ivec2 coords = ivec2(gl_FragCoord.xy);
+    vec4 colour = vec4(0.0);
+    for (int i = 0; i < samples; i++)
+        colour += texelFetch(scene, coords, i);
+    target = colour / float(samples);
+}
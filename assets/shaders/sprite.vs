@@ -3,16 +3,23 @@
 in vec2 position;
 in vec2 texCoords;
 
+// Per-instance data.
+in vec4 modelCol0;
+in vec4 modelCol1;
+in vec4 modelCol2;
+in vec4 modelCol3;
+in vec4 spriteColour;
+in vec4 uvRect;
+
 out vec2 TexCoords;
+out vec4 SpriteColour;
 
-uniform Locals {
-    vec4 spriteColour;
-    mat4 model;
-};
 uniform mat4 projection;
 
 void main()
 {
-    TexCoords = texCoords;
+    mat4 model = mat4(modelCol0, modelCol1, modelCol2, modelCol3);
+    TexCoords = uvRect.xy + (texCoords * uvRect.zw);
+    SpriteColour = spriteColour;
     gl_Position = projection * model * vec4(position, 0.0, 1.0);
 }
@@ -0,0 +1,144 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Compares finding the bricks in the ball's way by checking every brick in
+// the level (what we used to do), against only checking the bricks that
+// GameLevel's grid says are nearby. Run with `cargo bench`.
+
+extern crate cgmath;
+#[macro_use]
+extern crate criterion;
+extern crate rand;
+extern crate rust_gfx_breakout;
+
+use cgmath::vec2;
+use criterion::{Criterion, ParameterizedBenchmark};
+use rand::{Rng, SeedableRng};
+
+use rust_gfx_breakout::collision;
+use rust_gfx_breakout::game_level::GameLevel;
+
+
+const LEVEL_WIDTH: u32 = 800;
+const LEVEL_HEIGHT: u32 = 300;
+const BALL_RADIUS: f32 = 12.5;
+// How many ball paths to check against the level in each iteration.
+const PATHS: usize = 100;
+
+// (columns, rows). The first is the size of the built-in levels.
+const SIZES: &[(usize, usize)] = &[(15, 10), (40, 25), (100, 40)];
+
+// A level full of a mix of bricks, with every other row left empty.
+fn level(columns: usize, rows: usize) -> GameLevel {
+    let mut data = String::new();
+    for row in 0..rows {
+        let line: Vec<String> = (0..columns)
+            .map(|column| {
+                if row % 2 == 1 { 0 } else { 1 + (row + column) % 5 }
+            })
+            .map(|tile| tile.to_string())
+            .collect();
+        data.push_str(&line.join(" "));
+        data.push('\n');
+    }
+    GameLevel::parse(&data, LEVEL_WIDTH, LEVEL_HEIGHT).unwrap()
+}
+
+// Where the ball goes in one step. The same paths every time.
+fn paths() -> Vec<(cgmath::Vector2<f32>, cgmath::Vector2<f32>)> {
+    let mut rng = rand::XorShiftRng::from_seed([1, 2, 3, 4]);
+    (0..PATHS)
+        .map(|_| {
+            let center = vec2(
+                rng.gen_range(0.0, LEVEL_WIDTH as f32),
+                rng.gen_range(0.0, LEVEL_HEIGHT as f32));
+            // Up to twice the ball's usual speed, at 120 steps a second.
+            let motion = vec2(
+                rng.gen_range(-8.0, 8.0), rng.gen_range(-8.0, 8.0));
+            (center, motion)
+        })
+        .collect()
+}
+
+fn linear_scan(
+    level: &GameLevel,
+    center: cgmath::Vector2<f32>, motion: cgmath::Vector2<f32>)
+    -> usize
+{
+    level.bricks_iter()
+        .filter(|brick| !brick.is_destroyed())
+        .filter_map(|brick| {
+            let obj = brick.object();
+            collision::sweep_circle_aabb(
+                center, BALL_RADIUS, motion, obj.position, obj.size)
+        })
+        .count()
+}
+
+fn grid(
+    level: &GameLevel, nearby: &mut Vec<usize>,
+    center: cgmath::Vector2<f32>, motion: cgmath::Vector2<f32>)
+    -> usize
+{
+    let end = center + motion;
+    let reach = vec2(BALL_RADIUS, BALL_RADIUS);
+    level.bricks_near(
+        vec2(center.x.min(end.x), center.y.min(end.y)) - reach,
+        vec2(center.x.max(end.x), center.y.max(end.y)) + reach,
+        nearby);
+    nearby.iter()
+        .map(|&index| level.brick(index))
+        .filter(|brick| !brick.is_destroyed())
+        .filter_map(|brick| {
+            let obj = brick.object();
+            collision::sweep_circle_aabb(
+                center, BALL_RADIUS, motion, obj.position, obj.size)
+        })
+        .count()
+}
+
+// Both ways find the same bricks -- that's checked by the tests in
+// game_level and spatial_grid, so it isn't checked again here.
+fn brick_queries(c: &mut Criterion) {
+    let benchmark = ParameterizedBenchmark::new(
+        "linear scan",
+        |b, &&(columns, rows)| {
+            let level = level(columns, rows);
+            let paths = paths();
+            b.iter(|| {
+                paths.iter()
+                    .map(|&(center, motion)| {
+                        linear_scan(&level, center, motion)
+                    })
+                    .sum::<usize>()
+            })
+        },
+        SIZES)
+        .with_function("grid", |b, &&(columns, rows)| {
+            let level = level(columns, rows);
+            let paths = paths();
+            let mut nearby = Vec::new();
+            b.iter(|| {
+                paths.iter()
+                    .map(|&(center, motion)| {
+                        grid(&level, &mut nearby, center, motion)
+                    })
+                    .sum::<usize>()
+            })
+        });
+    c.bench("brick queries", benchmark);
+}
+
+criterion_group!(benches, brick_queries);
+criterion_main!(benches);
@@ -0,0 +1,292 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code. Sprite sheet animation for game objects.
+// Like the rest of the simulation this doesn't touch any graphics resources:
+// a frame is just the name of a sprite, and an animation's only job is to
+// say which name should be drawn right now.
+
+use std::rc::Rc;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayMode {
+    // Start again from the first frame after the last one.
+    Loop,
+    // Stop on the last frame.
+    Once,
+    // Go forwards to the last frame, then backwards to the first, and so on.
+    PingPong,
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub sprite: String,
+    // Seconds.
+    pub duration: f32,
+}
+
+// A named sequence of frames. Clips are never changed once they're made, so
+// lots of objects can share one.
+#[derive(Debug)]
+pub struct AnimationClip {
+    name: String,
+    frames: Vec<Frame>,
+    mode: PlayMode,
+}
+
+impl AnimationClip {
+    // Panics if there are no frames, or if any frame doesn't last for a
+    // positive amount of time. Either would be a bug in the game rather than
+    // something that can happen at runtime.
+    pub fn new(name: &str, frames: Vec<Frame>, mode: PlayMode) -> Self {
+        assert!(!frames.is_empty(), "animation {} has no frames", name);
+        assert!(frames.iter().all(|f| f.duration > 0.0),
+                "animation {} has a frame with no duration", name);
+        Self {
+            name: name.into(),
+            frames,
+            mode,
+        }
+    }
+
+    // A clip made from every frame of a sprite sheet, each lasting the same
+    // amount of time. The frames are the sprites that
+    // ResourceManager::load_sprite_sheet makes for a sheet with this name.
+    pub fn from_sheet(
+        sheet: &str, frame_count: usize, frame_duration: f32, mode: PlayMode)
+        -> Self
+    {
+        let frames = (0..frame_count)
+            .map(|i| Frame {
+                sprite: sheet_frame_name(sheet, i),
+                duration: frame_duration,
+            })
+            .collect();
+        Self::new(sheet, frames, mode)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    // The sprite for each frame, in order.
+    pub fn sprite_names(&self) -> Vec<String> {
+        self.frames.iter().map(|f| f.sprite.clone()).collect()
+    }
+}
+
+// The name of one frame in a sprite sheet.
+pub fn sheet_frame_name(sheet: &str, index: usize) -> String {
+    format!("{}_{}", sheet, index)
+}
+
+// One object's progress through a clip.
+#[derive(Clone, Debug)]
+pub struct Animation {
+    clip: Rc<AnimationClip>,
+    frame: usize,
+    // How long we've been on the current frame.
+    frame_time: f32,
+    // Only used by ping-pong clips.
+    backwards: bool,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(clip: Rc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            frame: 0,
+            frame_time: 0.0,
+            backwards: false,
+            finished: false,
+        }
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clip
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn sprite(&self) -> &str {
+        &self.clip.frames[self.frame].sprite
+    }
+
+    // Only clips that play once ever finish.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if self.finished {
+            return;
+        }
+
+        // A long frame (or a very short clip) might skip several frames at
+        // once, so keep going until we've used up all the time.
+        self.frame_time += delta_time;
+        while self.frame_time >= self.clip.frames[self.frame].duration {
+            self.frame_time -= self.clip.frames[self.frame].duration;
+            self.next_frame();
+            if self.finished {
+                self.frame_time = 0.0;
+                break;
+            }
+        }
+    }
+
+    fn next_frame(&mut self) {
+        let last = self.clip.frames.len() - 1;
+        match self.clip.mode {
+            PlayMode::Loop => {
+                self.frame = if self.frame == last { 0 } else { self.frame + 1 };
+            },
+            PlayMode::Once => {
+                if self.frame == last {
+                    self.finished = true;
+                }
+                else {
+                    self.frame += 1;
+                }
+            },
+            PlayMode::PingPong => {
+                if last == 0 {
+                    return;
+                }
+                if self.backwards && self.frame == 0 {
+                    self.backwards = false;
+                }
+                else if !self.backwards && self.frame == last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.frame -= 1;
+                }
+                else {
+                    self.frame += 1;
+                }
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three frames, a quarter of a second each.
+    fn animation(mode: PlayMode) -> Animation {
+        let clip = AnimationClip::from_sheet("sheet", 3, 0.25, mode);
+        Animation::new(Rc::new(clip))
+    }
+
+    // The frame after each of so many quarter-second steps.
+    fn frames(animation: &mut Animation, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.update(0.25);
+                animation.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_goes_back_to_the_start() {
+        let mut animation = animation(PlayMode::Loop);
+        assert_eq!(frames(&mut animation, 7), [1, 2, 0, 1, 2, 0, 1]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut animation = animation(PlayMode::Once);
+        assert_eq!(frames(&mut animation, 2), [1, 2]);
+        assert!(!animation.is_finished());
+        // It's only finished once the last frame has been shown for its
+        // whole duration.
+        assert_eq!(frames(&mut animation, 3), [2, 2, 2]);
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_each_end() {
+        let mut animation = animation(PlayMode::PingPong);
+        assert_eq!(frames(&mut animation, 8), [1, 2, 1, 0, 1, 2, 1, 0]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_with_one_frame_stays_put() {
+        let clip = AnimationClip::from_sheet(
+            "sheet", 1, 0.25, PlayMode::PingPong);
+        let mut animation = Animation::new(Rc::new(clip));
+        assert_eq!(frames(&mut animation, 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn time_carries_over_between_updates() {
+        let mut animation = animation(PlayMode::Loop);
+        animation.update(0.125);
+        assert_eq!(animation.frame(), 0);
+        animation.update(0.1875);
+        assert_eq!(animation.frame(), 1);
+        // 0.0625 into the second frame, so this isn't enough...
+        animation.update(0.125);
+        assert_eq!(animation.frame(), 1);
+        // ...but this is.
+        animation.update(0.0625);
+        assert_eq!(animation.frame(), 2);
+    }
+
+    #[test]
+    fn long_updates_skip_frames() {
+        let mut looping = animation(PlayMode::Loop);
+        looping.update(1.0);
+        assert_eq!(looping.frame(), 1);
+
+        let mut once = animation(PlayMode::Once);
+        once.update(10.0);
+        assert_eq!(once.frame(), 2);
+        assert!(once.is_finished());
+    }
+
+    #[test]
+    fn sheet_frames_are_named_in_order() {
+        let mut animation = animation(PlayMode::Loop);
+        assert_eq!(animation.sprite(), "sheet_0");
+        animation.update(0.25);
+        assert_eq!(animation.sprite(), "sheet_1");
+        assert_eq!(animation.clip().sprite_names(),
+                   ["sheet_0", "sheet_1", "sheet_2"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn clips_need_frames() {
+        AnimationClip::new("empty", Vec::new(), PlayMode::Loop);
+    }
+}
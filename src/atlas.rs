@@ -0,0 +1,422 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code. Rather than giving every sprite its own
+// texture, we pack lots of small images into a few big ones, so that the
+// sprite renderer can draw most of the scene without switching textures.
+//
+// Nothing in here knows about gfx -- this just lays out the images and
+// produces the combined pixels. The resource manager turns the result into
+// real textures.
+
+use image;
+
+use errors::*;
+
+
+// A region of an atlas, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// A very simple "shelf" packer. Images are placed left to right along a
+// shelf, and a new shelf is started underneath once the current one is full.
+// This wastes some space compared to the cleverer algorithms, but for a
+// handful of sprites it really doesn't matter, and it's easy to follow.
+// It does best when the images are inserted tallest first.
+pub struct AtlasPacker {
+    width: u32,
+    height: u32,
+    // Gap left around every image. See AtlasBuilder for why we want this.
+    padding: u32,
+    shelves: Vec<Shelf>,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    used: u32,
+}
+
+impl AtlasPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            shelves: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // Find room for an image of the given size. The returned rect doesn't
+    // include the padding. Returns None if there isn't space left.
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<Rect> {
+        let padded_w = width + self.padding * 2;
+        let padded_h = height + self.padding * 2;
+        if padded_w > self.width || padded_h > self.height {
+            return None;
+        }
+
+        // First try to fit it onto an existing shelf.
+        let atlas_width = self.width;
+        let padding = self.padding;
+        for shelf in &mut self.shelves {
+            if padded_h <= shelf.height && shelf.used + padded_w <= atlas_width {
+                let rect = Rect {
+                    x: shelf.used + padding,
+                    y: shelf.y + padding,
+                    width,
+                    height,
+                };
+                shelf.used += padded_w;
+                return Some(rect);
+            }
+        }
+
+        // Otherwise start a new shelf underneath the last one.
+        let y = self.shelves.last().map_or(0, |s| s.y + s.height);
+        if y + padded_h > self.height {
+            return None;
+        }
+        self.shelves.push(Shelf { y, height: padded_h, used: padded_w });
+        Some(Rect {
+            x: padding,
+            y: y + padding,
+            width,
+            height,
+        })
+    }
+}
+
+// One finished atlas image, and where each of the named images ended up in it.
+pub struct AtlasPage {
+    pub image: image::RgbaImage,
+    pub regions: Vec<(String, Rect)>,
+}
+
+impl AtlasPage {
+    // Convert a region to a UV rect of [left, top, width, height], which is
+    // what the sprite renderer wants.
+    pub fn uv_rect(&self, rect: &Rect) -> [f32; 4] {
+        let (w, h) = self.image.dimensions();
+        let (w, h) = (w as f32, h as f32);
+        [
+            rect.x as f32 / w,
+            rect.y as f32 / h,
+            rect.width as f32 / w,
+            rect.height as f32 / h,
+        ]
+    }
+}
+
+// Collects images, then packs them all at once into as many pages as it
+// takes.
+pub struct AtlasBuilder {
+    page_size: u32,
+    padding: u32,
+    images: Vec<(String, image::RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    // Pages are square, page_size pixels on each side.
+    // With bilinear filtering, sampling right at the edge of a sprite will
+    // blend in a little of whatever is next to it. To stop neighbouring
+    // sprites bleeding into each other, each image has its edge pixels
+    // stretched out into the padding around it.
+    pub fn new(page_size: u32, padding: u32) -> Self {
+        Self {
+            page_size,
+            padding,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: String, img: image::RgbaImage) {
+        self.images.push((name, img));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    // Empties the builder. Fails if any image is too big to ever fit on a
+    // page.
+    pub fn build(&mut self) -> Result<Vec<AtlasPage>> {
+        let mut images: Vec<_> = self.images.drain(..).collect();
+        // Tallest first packs best onto shelves.
+        images.sort_by(|a, b| b.1.height().cmp(&a.1.height()));
+
+        let mut pages: Vec<(AtlasPacker, AtlasPage)> = Vec::new();
+        for (name, img) in images {
+            let (w, h) = img.dimensions();
+            let mut placed = None;
+            for (i, page) in pages.iter_mut().enumerate() {
+                if let Some(rect) = page.0.insert(w, h) {
+                    placed = Some((i, rect));
+                    break;
+                }
+            }
+
+            let (i, rect) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut packer = AtlasPacker::new(
+                        self.page_size, self.page_size, self.padding);
+                    let rect = match packer.insert(w, h) {
+                        Some(rect) => rect,
+                        None => bail!(
+                            "image {} ({}x{}) is too big for a {}x{} atlas",
+                            name, w, h, self.page_size, self.page_size),
+                    };
+                    let page = AtlasPage {
+                        image: image::RgbaImage::new(
+                            self.page_size, self.page_size),
+                        regions: Vec::new(),
+                    };
+                    pages.push((packer, page));
+                    (pages.len() - 1, rect)
+                }
+            };
+
+            let page = &mut pages[i].1;
+            blit_extruded(&img, &mut page.image, &rect, self.padding);
+            page.regions.push((name, rect));
+        }
+
+        Ok(pages.into_iter().map(|(_, page)| page).collect())
+    }
+}
+
+// Cut a sprite sheet up into its frames, reading left to right then top to
+// bottom. Any leftover pixels at the right or bottom edge are ignored.
+pub fn split_sheet(
+    sheet: &image::RgbaImage, frame_width: u32, frame_height: u32)
+    -> Result<Vec<image::RgbaImage>>
+{
+    let (w, h) = sheet.dimensions();
+    if frame_width == 0 || frame_height == 0
+        || frame_width > w || frame_height > h
+    {
+        bail!("can't split a {}x{} sprite sheet into {}x{} frames",
+              w, h, frame_width, frame_height);
+    }
+
+    let mut frames = Vec::new();
+    for row in 0..(h / frame_height) {
+        for col in 0..(w / frame_width) {
+            let mut frame = image::RgbaImage::new(frame_width, frame_height);
+            for y in 0..frame_height {
+                for x in 0..frame_width {
+                    let pixel = *sheet.get_pixel(
+                        col * frame_width + x, row * frame_height + y);
+                    frame.put_pixel(x, y, pixel);
+                }
+            }
+            frames.push(frame);
+        }
+    }
+    Ok(frames)
+}
+
+// Copy src into dest at rect, filling the padding around it with copies of
+// the nearest edge pixel.
+fn blit_extruded(
+    src: &image::RgbaImage, dest: &mut image::RgbaImage, rect: &Rect,
+    padding: u32)
+{
+    let (w, h) = src.dimensions();
+    let x0 = rect.x - padding;
+    let y0 = rect.y - padding;
+    for dy in 0..(h + padding * 2) {
+        for dx in 0..(w + padding * 2) {
+            let sx = clamp_sub(dx, padding, w);
+            let sy = clamp_sub(dy, padding, h);
+            dest.put_pixel(x0 + dx, y0 + dy, *src.get_pixel(sx, sy));
+        }
+    }
+}
+
+// (value - offset), clamped to 0..limit.
+fn clamp_sub(value: u32, offset: u32, limit: u32) -> u32 {
+    if value < offset {
+        0
+    } else if value - offset >= limit {
+        limit - 1
+    } else {
+        value - offset
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every pixel is a different colour, so we can tell where it came from.
+    fn image(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba { data: [x as u8, y as u8, 255, 255] }
+        })
+    }
+
+    fn overlaps(a: &Rect, b: &Rect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width
+            && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn packer_fills_shelves_in_order() {
+        let mut packer = AtlasPacker::new(100, 100, 0);
+        assert_eq!(packer.insert(60, 40),
+                   Some(Rect { x: 0, y: 0, width: 60, height: 40 }));
+        assert_eq!(packer.insert(40, 30),
+                   Some(Rect { x: 60, y: 0, width: 40, height: 30 }));
+        // No room left on the first shelf, so it starts a new one.
+        assert_eq!(packer.insert(50, 50),
+                   Some(Rect { x: 0, y: 40, width: 50, height: 50 }));
+        // Too tall for what's left underneath, but fits on the second shelf.
+        assert_eq!(packer.insert(50, 20),
+                   Some(Rect { x: 50, y: 40, width: 50, height: 20 }));
+        assert_eq!(packer.insert(10, 20), None);
+    }
+
+    #[test]
+    fn packer_leaves_padding() {
+        let mut packer = AtlasPacker::new(100, 100, 2);
+        let a = packer.insert(10, 10).unwrap();
+        let b = packer.insert(10, 10).unwrap();
+        assert_eq!(a, Rect { x: 2, y: 2, width: 10, height: 10 });
+        assert_eq!(b, Rect { x: 16, y: 2, width: 10, height: 10 });
+        // Only fits without the padding.
+        assert_eq!(packer.insert(97, 10), None);
+        assert_eq!(AtlasPacker::new(100, 100, 2).insert(100, 100), None);
+    }
+
+    #[test]
+    fn builder_overflows_onto_another_page() {
+        let mut builder = AtlasBuilder::new(64, 1);
+        // Four of these fill a page exactly.
+        for i in 0..5 {
+            builder.add(format!("image{}", i), image(30, 30));
+        }
+        let pages = builder.build().unwrap();
+        assert!(builder.is_empty());
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].regions.len(), 4);
+        assert_eq!(pages[1].regions.len(), 1);
+
+        for page in &pages {
+            assert_eq!(page.image.dimensions(), (64, 64));
+            for (i, &(_, ref a)) in page.regions.iter().enumerate() {
+                for &(_, ref b) in &page.regions[i + 1..] {
+                    assert!(!overlaps(a, b), "{:?} and {:?}", a, b);
+                }
+            }
+        }
+        let mut names: Vec<_> = pages.iter()
+            .flat_map(|p| p.regions.iter().map(|r| r.0.clone()))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["image0", "image1", "image2", "image3", "image4"]);
+    }
+
+    #[test]
+    fn builder_rejects_images_that_never_fit() {
+        let mut builder = AtlasBuilder::new(64, 1);
+        builder.add("huge".into(), image(64, 10));
+        let error = builder.build().err().unwrap();
+        assert!(error.to_string().contains("huge"), "{}", error);
+    }
+
+    #[test]
+    fn builder_copies_the_pixels() {
+        let mut builder = AtlasBuilder::new(64, 2);
+        builder.add("a".into(), image(5, 3));
+        let pages = builder.build().unwrap();
+        let (_, rect) = pages[0].regions[0];
+        let src = image(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                assert_eq!(pages[0].image.get_pixel(rect.x + x, rect.y + y),
+                           src.get_pixel(x, y));
+            }
+        }
+        assert_eq!(pages[0].uv_rect(&rect),
+                   [2.0 / 64.0, 2.0 / 64.0, 5.0 / 64.0, 3.0 / 64.0]);
+    }
+
+    #[test]
+    fn edges_are_extruded_into_the_padding() {
+        let src = image(3, 2);
+        let mut dest = image::RgbaImage::new(10, 10);
+        let rect = Rect { x: 2, y: 2, width: 3, height: 2 };
+        blit_extruded(&src, &mut dest, &rect, 2);
+
+        let at = |x, y| *dest.get_pixel(x, y);
+        // The corners of the padding are copies of the corner pixels.
+        assert_eq!(at(0, 0), *src.get_pixel(0, 0));
+        assert_eq!(at(6, 0), *src.get_pixel(2, 0));
+        assert_eq!(at(0, 5), *src.get_pixel(0, 1));
+        assert_eq!(at(6, 5), *src.get_pixel(2, 1));
+        // The sides are copies of the edge next to them.
+        assert_eq!(at(3, 0), *src.get_pixel(1, 0));
+        assert_eq!(at(3, 5), *src.get_pixel(1, 1));
+        assert_eq!(at(0, 3), *src.get_pixel(0, 1));
+        assert_eq!(at(6, 2), *src.get_pixel(2, 0));
+        // The image itself is untouched.
+        assert_eq!(at(3, 3), *src.get_pixel(1, 1));
+        // Nothing outside the padding is drawn on.
+        assert_eq!(at(7, 7), image::Rgba { data: [0, 0, 0, 0] });
+        assert_eq!(at(7, 0), image::Rgba { data: [0, 0, 0, 0] });
+    }
+
+    #[test]
+    fn sheets_split_into_frames() {
+        // 3 by 2 frames, with some left over at the right and bottom.
+        let sheet = image(10, 7);
+        let frames = split_sheet(&sheet, 3, 3).unwrap();
+        assert_eq!(frames.len(), 6);
+        for (i, frame) in frames.iter().enumerate() {
+            let (col, row) = (i as u32 % 3, i as u32 / 3);
+            assert_eq!(frame.dimensions(), (3, 3));
+            assert_eq!(frame.get_pixel(0, 0),
+                       sheet.get_pixel(col * 3, row * 3));
+            assert_eq!(frame.get_pixel(2, 1),
+                       sheet.get_pixel(col * 3 + 2, row * 3 + 1));
+        }
+    }
+
+    #[test]
+    fn sheets_need_sensible_frames() {
+        let sheet = image(10, 7);
+        assert!(split_sheet(&sheet, 0, 3).is_err());
+        assert!(split_sheet(&sheet, 3, 0).is_err());
+        assert!(split_sheet(&sheet, 11, 3).is_err());
+        assert!(split_sheet(&sheet, 3, 8).is_err());
+        // One frame the size of the whole sheet is fine.
+        assert_eq!(split_sheet(&sheet, 10, 7).unwrap().len(), 1);
+    }
+}
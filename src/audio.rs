@@ -0,0 +1,208 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// The original code used irrKlang, and played sounds straight from their
+// file paths. Here sounds are loaded up front and given a name, just like
+// textures, and the game asks for them by name.
+//
+// The actual playing is done by an AudioBackend. RodioAudio really plays
+// sounds, NullAudio ignores them, and RecordingAudio remembers which sounds
+// were played so that tests can check them without any audio hardware.
+//
+// An AudioManager is a cheap, cloneable handle to a backend. The game and the
+// resource manager both hold one, so that resources can be loaded in one
+// place and played from another.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use rodio;
+
+use errors::*;
+
+
+pub trait AudioBackend {
+    fn load(&mut self, path: &Path, name: &str) -> Result<()>;
+    // Play a sound once. Unknown names are ignored.
+    fn play(&mut self, name: &str);
+    // Play a sound over and over, replacing any music that's already
+    // playing.
+    fn play_looped(&mut self, name: &str);
+}
+
+
+#[derive(Clone)]
+pub struct AudioManager {
+    backend: Rc<RefCell<Box<dyn AudioBackend>>>,
+}
+
+impl AudioManager {
+    pub fn new<B: AudioBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Rc::new(RefCell::new(Box::new(backend))),
+        }
+    }
+
+    // An audio manager that doesn't play anything.
+    pub fn null() -> Self {
+        Self::new(NullAudio)
+    }
+
+    pub fn load_sound<P: AsRef<Path>>(&self, path: &P, name: String)
+        -> Result<()>
+    {
+        self.backend.borrow_mut().load(path.as_ref(), &name)
+    }
+
+    pub fn play_sound(&self, name: &str) {
+        self.backend.borrow_mut().play(name);
+    }
+
+    pub fn play_music(&self, name: &str) {
+        self.backend.borrow_mut().play_looped(name);
+    }
+}
+
+
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn load(&mut self, _path: &Path, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn play(&mut self, _name: &str) {}
+
+    fn play_looped(&mut self, _name: &str) {}
+}
+
+
+// The names of the sounds that have been played, oldest first.
+pub type SoundLog = Rc<RefCell<Vec<String>>>;
+
+// Doesn't load or play anything, but keeps a log of what was played. Looped
+// sounds are logged too, since the log doesn't care how long they last.
+pub struct RecordingAudio {
+    log: SoundLog,
+}
+
+impl RecordingAudio {
+    pub fn new() -> Self {
+        Self {
+            log: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // Keep hold of this before giving the backend to an AudioManager.
+    pub fn log(&self) -> SoundLog {
+        self.log.clone()
+    }
+}
+
+impl Default for RecordingAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for RecordingAudio {
+    fn load(&mut self, _path: &Path, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn play(&mut self, name: &str) {
+        self.log.borrow_mut().push(name.into());
+    }
+
+    fn play_looped(&mut self, name: &str) {
+        self.log.borrow_mut().push(name.into());
+    }
+}
+
+
+// Sounds are kept in memory in their encoded form, and decoded each time
+// they are played. They're small, so that's not much work.
+// rodio wants to own the data it's decoding, so we share it with an Arc.
+#[derive(Clone)]
+struct SoundData(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SoundData {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+pub struct RodioAudio {
+    device: rodio::Device,
+    sounds: HashMap<String, SoundData>,
+    // Dropping the sink stops the music.
+    music: Option<rodio::Sink>,
+}
+
+impl RodioAudio {
+    pub fn new() -> Result<Self> {
+        let device = rodio::default_output_device()
+            .ok_or("no audio output device")?;
+        Ok(Self {
+            device,
+            sounds: HashMap::with_capacity(10),
+            music: None,
+        })
+    }
+
+    fn decoder(&self, name: &str)
+        -> Option<rodio::Decoder<io::Cursor<SoundData>>>
+    {
+        let data = self.sounds.get(name)?.clone();
+        rodio::Decoder::new(io::Cursor::new(data)).ok()
+    }
+}
+
+impl AudioBackend for RodioAudio {
+    fn load(&mut self, path: &Path, name: &str) -> Result<()> {
+        use std::fs;
+        use std::io::Read;
+
+        let mut data = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut data)?;
+        let data = SoundData(Arc::new(data));
+        // Make sure it decodes now, rather than finding out when we try to
+        // play it.
+        rodio::Decoder::new(io::Cursor::new(data.clone()))?;
+        self.sounds.insert(name.into(), data);
+        Ok(())
+    }
+
+    fn play(&mut self, name: &str) {
+        use self::rodio::Source;
+
+        if let Some(decoder) = self.decoder(name) {
+            rodio::play_raw(&self.device, decoder.convert_samples());
+        }
+    }
+
+    fn play_looped(&mut self, name: &str) {
+        use self::rodio::Source;
+
+        if let Some(decoder) = self.decoder(name) {
+            let sink = rodio::Sink::new(&self.device);
+            sink.append(decoder.repeat_infinite());
+            self.music = Some(sink);
+        }
+    }
+}
@@ -26,7 +26,13 @@ extern crate gfx_window_glfw;
 extern crate glfw;
 
 extern crate rust_gfx_breakout as breakout;
+use breakout::audio;
+use breakout::config::{CONFIG_FILE, ConfigWatcher, GameConfig};
 use breakout::errors::*;
+use breakout::game::GameEvent;
+use breakout::high_scores::HighScores;
+use breakout::input::{Bindings, InputState};
+use breakout::timestep::FixedTimestep;
 
 
 type EventQueue = ::std::sync::mpsc::Receiver<(f64, glfw::WindowEvent)>;
@@ -42,6 +48,17 @@ type RenderTargetView<R: gfx::Resources> =
 type DepthStencilView<R: gfx::Resources> =
     gfx::handle::DepthStencilView<R, DepthFormat>;
 
+// Anti-aliasing for the game scene. Use 1 to turn it off.
+const MSAA_SAMPLES: u8 = 4;
+
+// The game is always updated in steps of this many seconds, however fast or
+// slow the frames are.
+const TIMESTEP: f32 = 1.0 / 120.0;
+// If we fall further behind than this, give up trying to catch up.
+const MAX_STEPS_PER_FRAME: u32 = 10;
+// How often to check whether the config file has been changed, in seconds.
+const CONFIG_POLL_INTERVAL: f64 = 0.5;
+
 const NUM_KEYS: usize = 150;  // Roughly this many keys on the keyboard.
 type KeyMap = HashMap<glfw::Key, bool>;
 
@@ -56,35 +73,105 @@ struct Gfx<C: gfx::CommandBuffer<R>, D: gfx::Device, F: gfx::traits::FactoryExt<
 }
 
 fn run() -> Result<()> {
+    if ::std::env::args().skip(1).any(|arg| arg == "--check-levels") {
+        return check_levels();
+    }
+
     let (events, mut glfw, mut window, mut gfx) = setup_gl_window_and_gfx()?;
     let (fb_width, fb_height) = window.get_framebuffer_size();
     let mut keys: KeyMap = KeyMap::with_capacity(NUM_KEYS);
 
     // TODO: set viewport.
 
+    // The game can still be played without sound, so carry on if there's
+    // no audio device.
+    let audio = match audio::RodioAudio::new() {
+        Ok(backend) => audio::AudioManager::new(backend),
+        Err(e) => {
+            eprintln!("audio disabled: {}", e);
+            audio::AudioManager::null()
+        },
+    };
+
+    // A broken config file shouldn't stop the game, the defaults are fine.
+    let config = GameConfig::load(&CONFIG_FILE).unwrap_or_else(|e| {
+        eprintln!("{}", error_messages(&e));
+        eprintln!("using the default config");
+        GameConfig::default()
+    });
+    let mut config_watcher = ConfigWatcher::new(&CONFIG_FILE);
+    let mut bindings = Bindings::new(&config.controls);
+
     // Initialize game
-    let mut breakout = breakout::Game::new(
-        fb_width, fb_height, gfx.factory.clone(), gfx.colour_view.clone())?;
+    let mut breakout = breakout::Game::new(fb_width, fb_height, config)?;
+    breakout.set_audio(audio.clone());
+    // Losing the high scores isn't a reason not to play.
+    match HighScores::load(&HighScores::default_path()) {
+        Ok(high_scores) => {
+            if let Some(backup) = high_scores.backup() {
+                eprintln!("high scores were corrupted, moved them to {}",
+                          backup.display());
+            }
+            breakout.set_high_scores(high_scores);
+        },
+        Err(e) => eprintln!("high scores disabled: {}", e),
+    }
+    let mut renderer = breakout::GameRenderer::new(
+        fb_width, fb_height, MSAA_SAMPLES,
+        gfx.factory.clone(), gfx.colour_view.clone(),
+        audio.clone(), &breakout)?;
+    audio.play_music("music");
 
-    let mut delta_time;
-    let mut last_frame = 0.0;
+    let mut timestep = FixedTimestep::new(TIMESTEP, MAX_STEPS_PER_FRAME);
+    // Start timing from here, so the time spent loading doesn't count as
+    // part of the first frame.
+    let mut last_frame = glfw.get_time();
+    let mut last_config_poll = last_frame;
 
     while !window.should_close() {
         // Calculate delta time.
         // If we wanted to do this without relying on GLFW we could use
         // std::time::Instant.
-        let current_frame = glfw.get_time() as f32;
-        delta_time = current_frame - last_frame;
+        let current_frame = glfw.get_time();
+        let delta_time = current_frame - last_frame;
         last_frame = current_frame;
         process_events(&mut glfw, &events, &mut keys, &mut window);
 
-        breakout.process_input(delta_time, &keys);
-        breakout.update(delta_time);
+        // Pick up any changes to the config while the game is running, so it
+        // can be tuned without restarting. If the new config is broken, keep
+        // using the old one until it's fixed.
+        if current_frame - last_config_poll >= CONFIG_POLL_INTERVAL {
+            last_config_poll = current_frame;
+            match config_watcher.poll() {
+                Some(Ok(config)) => {
+                    println!("reloaded {}", CONFIG_FILE);
+                    bindings = Bindings::new(&config.controls);
+                    breakout.set_config(config);
+                },
+                Some(Err(e)) => eprintln!("{}", error_messages(&e)),
+                None => {},
+            }
+        }
+
+        // Every step in a frame sees the same input, since we only find out
+        // about key presses once per frame.
+        let input = input_state(&bindings, &keys);
+        for _ in 0..timestep.advance(delta_time) {
+            breakout.step(timestep.step(), &input);
+        }
+        let events: Vec<_> = breakout.drain_events().collect();
+        for event in events {
+            if let GameEvent::HighScore(_) = event {
+                if let Err(e) = breakout.high_scores().save() {
+                    eprintln!("couldn't save high scores: {}", e);
+                }
+            }
+        }
 
         gfx.encoder.clear(&gfx.colour_view, [0.0, 0.0, 0.0, 1.0]);
         // Depth buffer is not actually used, but if it were...
         gfx.encoder.clear_depth(&gfx.depth_view, 1.0);
-        breakout.render(&mut gfx.encoder);
+        renderer.render(&breakout, timestep.alpha(), &mut gfx.encoder);
         gfx.encoder.flush(&mut gfx.device);
 
         {
@@ -99,6 +186,122 @@ fn run() -> Result<()> {
     Ok(())
 }
 
+// Load every level without opening a window, and report all the problems
+// rather than stopping at the first one.
+fn check_levels() -> Result<()> {
+    use breakout::game::LEVEL_FILES;
+    use breakout::game_level::GameLevel;
+
+    // The size only affects where the bricks end up, not whether the level
+    // is valid.
+    const LEVEL_WIDTH: u32 = 800;
+    const LEVEL_HEIGHT: u32 = 300;
+
+    let mut failed = 0;
+    for path in LEVEL_FILES.iter() {
+        match GameLevel::new(path, LEVEL_WIDTH, LEVEL_HEIGHT) {
+            Ok(_) => println!("{}: ok", path),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}", error_messages(&e));
+            },
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} levels failed to load", failed, LEVEL_FILES.len());
+    }
+    Ok(())
+}
+
+// The error and everything that led to it, on one line.
+fn error_messages(error: &Error) -> String {
+    let messages: Vec<_> = error.iter().map(|e| e.to_string()).collect();
+    messages.join(": ")
+}
+
+// The game only knows about actions, so work out which ones the held keys
+// are doing.
+fn input_state(bindings: &Bindings, keys: &KeyMap) -> InputState {
+    let keys_down = keys.iter()
+        .filter(|&(_, &down)| down)
+        .filter_map(|(&key, _)| key_name(key));
+    bindings.input_state(keys_down)
+}
+
+// What the game calls each of the keys that can be bound. These have to be
+// the same as input::KEY_NAMES, in the same order -- the test at the bottom
+// checks. Any other keys are ignored.
+const KEY_NAMES: &[(glfw::Key, &str)] = &[
+    (glfw::Key::A, "A"),
+    (glfw::Key::B, "B"),
+    (glfw::Key::C, "C"),
+    (glfw::Key::D, "D"),
+    (glfw::Key::E, "E"),
+    (glfw::Key::F, "F"),
+    (glfw::Key::G, "G"),
+    (glfw::Key::H, "H"),
+    (glfw::Key::I, "I"),
+    (glfw::Key::J, "J"),
+    (glfw::Key::K, "K"),
+    (glfw::Key::L, "L"),
+    (glfw::Key::M, "M"),
+    (glfw::Key::N, "N"),
+    (glfw::Key::O, "O"),
+    (glfw::Key::P, "P"),
+    (glfw::Key::Q, "Q"),
+    (glfw::Key::R, "R"),
+    (glfw::Key::S, "S"),
+    (glfw::Key::T, "T"),
+    (glfw::Key::U, "U"),
+    (glfw::Key::V, "V"),
+    (glfw::Key::W, "W"),
+    (glfw::Key::X, "X"),
+    (glfw::Key::Y, "Y"),
+    (glfw::Key::Z, "Z"),
+    (glfw::Key::Num0, "0"),
+    (glfw::Key::Num1, "1"),
+    (glfw::Key::Num2, "2"),
+    (glfw::Key::Num3, "3"),
+    (glfw::Key::Num4, "4"),
+    (glfw::Key::Num5, "5"),
+    (glfw::Key::Num6, "6"),
+    (glfw::Key::Num7, "7"),
+    (glfw::Key::Num8, "8"),
+    (glfw::Key::Num9, "9"),
+    (glfw::Key::Kp0, "Kp0"),
+    (glfw::Key::Kp1, "Kp1"),
+    (glfw::Key::Kp2, "Kp2"),
+    (glfw::Key::Kp3, "Kp3"),
+    (glfw::Key::Kp4, "Kp4"),
+    (glfw::Key::Kp5, "Kp5"),
+    (glfw::Key::Kp6, "Kp6"),
+    (glfw::Key::Kp7, "Kp7"),
+    (glfw::Key::Kp8, "Kp8"),
+    (glfw::Key::Kp9, "Kp9"),
+    (glfw::Key::KpEnter, "KpEnter"),
+    (glfw::Key::Space, "Space"),
+    (glfw::Key::Enter, "Enter"),
+    (glfw::Key::Tab, "Tab"),
+    (glfw::Key::Backspace, "Backspace"),
+    (glfw::Key::Left, "Left"),
+    (glfw::Key::Right, "Right"),
+    (glfw::Key::Up, "Up"),
+    (glfw::Key::Down, "Down"),
+    (glfw::Key::LeftShift, "LeftShift"),
+    (glfw::Key::RightShift, "RightShift"),
+    (glfw::Key::LeftControl, "LeftControl"),
+    (glfw::Key::RightControl, "RightControl"),
+    (glfw::Key::LeftAlt, "LeftAlt"),
+    (glfw::Key::RightAlt, "RightAlt"),
+];
+
+fn key_name(key: glfw::Key) -> Option<&'static str> {
+    KEY_NAMES.iter()
+        .find(|&&(k, _)| k == key)
+        .map(|&(_, name)| name)
+}
+
 fn process_events(glfw: &mut glfw::Glfw, events: &EventQueue,
                   keys: &mut KeyMap, window: &mut glfw::Window)
 {
@@ -170,3 +373,21 @@ fn setup_gl_window_and_gfx()
 }
 
 quick_main!(run);
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use breakout::input;
+
+    #[test]
+    fn key_names_match_the_game() {
+        let names: Vec<&str> = KEY_NAMES.iter().map(|&(_, n)| n).collect();
+        assert_eq!(names, input::KEY_NAMES);
+        // And no key is listed twice.
+        for (i, &(key, _)) in KEY_NAMES.iter().enumerate() {
+            assert_eq!(KEY_NAMES.iter().position(|&(k, _)| k == key), Some(i));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// In the original code a brick was just a GameObject, and was destroyed the
+// first time it was hit. Bricks can now take several hits, so they need to
+// remember how much damage they've taken.
+
+use std::rc::Rc;
+
+use cgmath;
+
+use animation::AnimationClip;
+use game_object::GameObject;
+
+
+// What happened when the ball hit a brick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hit {
+    // Solid bricks can't be damaged.
+    Solid,
+    // The brick lost a hit point but is still there.
+    Damaged,
+    Destroyed,
+}
+
+#[derive(Clone)]
+pub struct Brick {
+    obj: GameObject,
+    hit_points: u32,
+    max_hit_points: u32,
+    // Scored when the brick is destroyed.
+    points: u32,
+    // The colour when undamaged. The brick gets darker as it takes damage.
+    colour: cgmath::Vector3<f32>,
+}
+
+impl Brick {
+    // A damaged brick is never darker than this fraction of its colour, so
+    // it stays visible against the background.
+    const MIN_BRIGHTNESS: f32 = 0.4;
+
+    pub fn new(obj: GameObject, hit_points: u32, points: u32) -> Self {
+        let hit_points = hit_points.max(1);
+        Self {
+            colour: obj.colour,
+            obj,
+            hit_points,
+            max_hit_points: hit_points,
+            points,
+        }
+    }
+
+    pub fn object(&self) -> &GameObject {
+        &self.obj
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.obj.is_solid
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.obj.is_destroyed
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.obj.is_visible()
+    }
+
+    pub fn hit_points(&self) -> u32 {
+        self.hit_points
+    }
+
+    pub fn max_hit_points(&self) -> u32 {
+        self.max_hit_points
+    }
+
+    pub fn points(&self) -> u32 {
+        self.points
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.hit_points < self.max_hit_points
+    }
+
+    pub fn hit(&mut self) -> Hit {
+        if self.obj.is_solid {
+            return Hit::Solid;
+        }
+        self.hit_points = self.hit_points.saturating_sub(1);
+        if self.hit_points == 0 {
+            self.obj.is_destroyed = true;
+            Hit::Destroyed
+        }
+        else {
+            let health = self.hit_points as f32 / self.max_hit_points as f32;
+            let brightness =
+                Self::MIN_BRIGHTNESS + (1.0 - Self::MIN_BRIGHTNESS) * health;
+            self.obj.colour = self.colour * brightness;
+            Hit::Damaged
+        }
+    }
+
+    // Destroy the brick however many hit points it has left, e.g. when the
+    // ball is passing straight through. Solid bricks can't be destroyed.
+    pub fn smash(&mut self) -> Hit {
+        if self.obj.is_solid {
+            return Hit::Solid;
+        }
+        self.hit_points = 0;
+        self.obj.is_destroyed = true;
+        Hit::Destroyed
+    }
+
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        self.obj.play(clip);
+    }
+
+    pub fn animate(&mut self, delta_time: f32) {
+        self.obj.animate(delta_time);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::{vec2, vec3};
+
+    fn brick(hit_points: u32, solid: bool) -> Brick {
+        let mut obj = GameObject::new(
+            vec2(0.0, 0.0), vec2(10.0, 10.0), "block", vec3(1.0, 0.5, 0.0));
+        obj.is_solid = solid;
+        Brick::new(obj, hit_points, 50)
+    }
+
+    fn close(a: cgmath::Vector3<f32>, b: cgmath::Vector3<f32>) -> bool {
+        (a - b).x.abs() < 1e-5 && (a - b).y.abs() < 1e-5
+            && (a - b).z.abs() < 1e-5
+    }
+
+    #[test]
+    fn one_hit_destroys_a_normal_brick() {
+        let mut brick = brick(1, false);
+        assert_eq!(brick.hit(), Hit::Destroyed);
+        assert!(brick.is_destroyed());
+        assert_eq!(brick.hit_points(), 0);
+    }
+
+    #[test]
+    fn tough_bricks_darken_until_destroyed() {
+        let mut brick = brick(4, false);
+        assert!(!brick.is_damaged());
+        // Three quarters, half, then a quarter of the way from the minimum
+        // brightness to full.
+        for &brightness in &[0.85, 0.7, 0.55] {
+            assert_eq!(brick.hit(), Hit::Damaged);
+            assert!(brick.is_damaged());
+            assert!(!brick.is_destroyed());
+            let expected = vec3(1.0, 0.5, 0.0) * brightness;
+            assert!(close(brick.object().colour, expected),
+                    "{:?} should be {:?}", brick.object().colour, expected);
+        }
+        assert_eq!(brick.hit_points(), 1);
+        assert_eq!(brick.hit(), Hit::Destroyed);
+        assert!(brick.is_destroyed());
+    }
+
+    #[test]
+    fn solid_bricks_stay() {
+        let mut brick = brick(1, true);
+        for _ in 0..10 {
+            assert_eq!(brick.hit(), Hit::Solid);
+        }
+        assert_eq!(brick.smash(), Hit::Solid);
+        assert!(!brick.is_destroyed());
+        assert!(!brick.is_damaged());
+        assert_eq!(brick.object().colour, vec3(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn smashing_destroys_straight_away() {
+        let mut brick = brick(4, false);
+        brick.hit();
+        assert_eq!(brick.smash(), Hit::Destroyed);
+        assert!(brick.is_destroyed());
+        assert_eq!(brick.hit_points(), 0);
+    }
+
+    #[test]
+    fn at_least_one_hit_point() {
+        let mut brick = brick(0, false);
+        assert_eq!(brick.max_hit_points(), 1);
+        assert_eq!(brick.hit(), Hit::Destroyed);
+    }
+}
@@ -13,51 +13,1025 @@
 // The original code was modified by Adrian Chan in order to port it to Rust.
 
 use cgmath;
+use cgmath::{InnerSpace, vec2};
 
 
-type Difference = cgmath::Vector2<f32>;
+type Vec2 = cgmath::Vector2<f32>;
 
-pub enum Collision {
-    Yes(Direction, Difference),
-    No,
+// Not part of the original code, which only had the circle vs box test the
+// ball needed. There's now a small set of shapes, and any two of them can be
+// checked for an overlap.
+
+// An axis-aligned box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    // From the top-left corner and size, the way GameObjects are laid out.
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            min: position,
+            max: position + size,
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    // Touching counts as overlapping.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+// A box that can be turned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrientedRect {
+    pub center: Vec2,
+    // Half the width and height, before it's turned.
+    pub half_size: Vec2,
+    // In radians. Positive is clockwise on screen, since y points down.
+    pub angle: f32,
+}
+
+impl OrientedRect {
+    // Clockwise from the top-left corner (before it's turned).
+    pub fn corners(&self) -> [Vec2; 4] {
+        let (sin, cos) = self.angle.sin_cos();
+        let x_axis = vec2(cos, sin) * self.half_size.x;
+        let y_axis = vec2(-sin, cos) * self.half_size.y;
+        [
+            self.center - x_axis - y_axis,
+            self.center + x_axis - y_axis,
+            self.center + x_axis + y_axis,
+            self.center - x_axis + y_axis,
+        ]
+    }
+}
+
+// A line with rounded ends, i.e. every point within radius of the line
+// between start and end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Capsule {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Aabb(Aabb),
+    Circle(Circle),
+    OrientedRect(OrientedRect),
+    Capsule(Capsule),
+}
+
+impl Shape {
+    // The smallest axis-aligned box that the shape fits in.
+    pub fn bounds(&self) -> Aabb {
+        let core = Core::from(self);
+        let reach = vec2(core.radius, core.radius);
+        let points = core.points();
+        let mut bounds = Aabb { min: points[0], max: points[0] };
+        for point in &points[1..] {
+            bounds.min.x = bounds.min.x.min(point.x);
+            bounds.min.y = bounds.min.y.min(point.y);
+            bounds.max.x = bounds.max.x.max(point.x);
+            bounds.max.y = bounds.max.y.max(point.y);
+        }
+        bounds.min -= reach;
+        bounds.max += reach;
+        bounds
+    }
+}
+
+impl From<Aabb> for Shape {
+    fn from(aabb: Aabb) -> Self {
+        Shape::Aabb(aabb)
+    }
+}
+
+impl From<Circle> for Shape {
+    fn from(circle: Circle) -> Self {
+        Shape::Circle(circle)
+    }
+}
+
+impl From<OrientedRect> for Shape {
+    fn from(rect: OrientedRect) -> Self {
+        Shape::OrientedRect(rect)
+    }
+}
+
+impl From<Capsule> for Shape {
+    fn from(capsule: Capsule) -> Self {
+        Shape::Capsule(capsule)
+    }
+}
+
+// How two shapes overlap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Overlap {
+    // Roughly where they touch. This is the deepest point of the first shape
+    // inside the second one.
+    pub point: Vec2,
+    // The direction to move the first shape to get it out of the second.
+    // Unit length.
+    pub normal: Vec2,
+    // How far the first shape would have to move along normal. Zero if they
+    // are only just touching.
+    pub depth: f32,
+}
+
+// Find out whether two shapes overlap, and if so by how much. Touching counts
+// as overlapping.
+//
+// Every shape is treated as a core (a point, a line, or a convex polygon)
+// grown by a radius: a circle is a point grown by its radius, a capsule is a
+// line grown by its radius, and boxes aren't grown at all. If the cores don't
+// overlap, it comes down to whether they're closer together than the two
+// radii. If they do, the separating axis test finds the shortest way out.
+pub fn overlap(a: &Shape, b: &Shape) -> Option<Overlap> {
+    let a = Core::from(a);
+    let b = Core::from(b);
+    let radii = a.radius + b.radius;
+
+    match separation(&a, &b) {
+        Some((depth, normal)) => {
+            let point = a.support(-normal) - normal * a.radius;
+            Some(Overlap { point, normal, depth: depth + radii })
+        },
+        None => {
+            let (on_a, on_b) = closest_points(&a, &b);
+            let offset = on_a - on_b;
+            let distance = offset.magnitude();
+            if distance > radii {
+                return None;
+            }
+            // Two circles with the same center could go either way. Up is
+            // as good as anything.
+            let normal = if distance > 0.0 {
+                offset / distance
+            } else {
+                vec2(0.0, -1.0)
+            };
+            Some(Overlap {
+                point: on_a - normal * a.radius,
+                normal,
+                depth: radii - distance,
+            })
+        },
+    }
+}
+
+// A shape as a core and a radius, see overlap.
+struct Core {
+    // Only the first count are used. 1 for a point, 2 for a line, more for a
+    // polygon (which must be convex and go round in order).
+    points: [Vec2; 4],
+    count: usize,
+    radius: f32,
+}
+
+impl<'a> From<&'a Shape> for Core {
+    fn from(shape: &'a Shape) -> Self {
+        let zero = vec2(0.0, 0.0);
+        match *shape {
+            Shape::Aabb(ref aabb) => Core {
+                points: [
+                    aabb.min, vec2(aabb.max.x, aabb.min.y),
+                    aabb.max, vec2(aabb.min.x, aabb.max.y)],
+                count: 4,
+                radius: 0.0,
+            },
+            Shape::Circle(ref circle) => Core {
+                points: [circle.center, zero, zero, zero],
+                count: 1,
+                radius: circle.radius,
+            },
+            Shape::OrientedRect(ref rect) => Core {
+                points: rect.corners(),
+                count: 4,
+                radius: 0.0,
+            },
+            Shape::Capsule(ref capsule) => Core {
+                points: [capsule.start, capsule.end, zero, zero],
+                count: 2,
+                radius: capsule.radius,
+            },
+        }
+    }
+}
+
+impl Core {
+    fn points(&self) -> &[Vec2] {
+        &self.points[..self.count]
+    }
+
+    // Each edge as (start, end). A line is one edge, a point has none.
+    fn edges(&self) -> Vec<(Vec2, Vec2)> {
+        match self.count {
+            1 => Vec::new(),
+            2 => vec![(self.points[0], self.points[1])],
+            n => (0..n).map(|i| (self.points[i], self.points[(i + 1) % n]))
+                .collect(),
+        }
+    }
+
+    // The directions the separating axis test has to try for this core. For
+    // a polygon that's the normal of each edge. A line needs its own
+    // direction as well, so that things just off the ends don't look like
+    // they're touching it.
+    fn axes(&self) -> Vec<Vec2> {
+        let mut axes = Vec::with_capacity(self.count);
+        for (start, end) in self.edges() {
+            let along = end - start;
+            if along.magnitude2() > 0.0 {
+                axes.push(vec2(-along.y, along.x).normalize());
+                if self.count == 2 {
+                    axes.push(along.normalize());
+                }
+            }
+        }
+        axes
+    }
+
+    // (min, max) of the core's points along an axis.
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        let mut min = ::std::f32::INFINITY;
+        let mut max = ::std::f32::NEG_INFINITY;
+        for point in self.points() {
+            let distance = point.dot(axis);
+            min = min.min(distance);
+            max = max.max(distance);
+        }
+        (min, max)
+    }
+
+    // The point of the core furthest in a direction.
+    fn support(&self, direction: Vec2) -> Vec2 {
+        let mut best = self.points[0];
+        for &point in &self.points()[1..] {
+            if point.dot(direction) > best.dot(direction) {
+                best = point;
+            }
+        }
+        best
+    }
+}
+
+// If the cores overlap, how far (and in which direction) a has to move to
+// get out of b. None if they don't, or if neither core has any axes to test
+// (two points), in which case closest_points can tell.
+fn separation(a: &Core, b: &Core) -> Option<(f32, Vec2)> {
+    let mut axes = a.axes();
+    axes.extend(b.axes());
+    if axes.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(f32, Vec2)> = None;
+    for axis in axes {
+        let (a_min, a_max) = a.project(axis);
+        let (b_min, b_max) = b.project(axis);
+        let depth = (a_max - b_min).min(b_max - a_min);
+        if depth < 0.0 {
+            return None;
+        }
+        if best.map_or(true, |(best_depth, _)| depth < best_depth) {
+            // Push a away from b.
+            let normal =
+                if a_min + a_max >= b_min + b_max { axis } else { -axis };
+            best = Some((depth, normal));
+        }
+    }
+    best
+}
+
+// The closest pair of points on two cores that don't overlap.
+fn closest_points(a: &Core, b: &Core) -> (Vec2, Vec2) {
+    let mut best = (a.points[0], b.points[0]);
+    let mut best_distance = (best.0 - best.1).magnitude2();
+    {
+        let mut consider = |on_a: Vec2, on_b: Vec2| {
+            let distance = (on_a - on_b).magnitude2();
+            if distance < best_distance {
+                best = (on_a, on_b);
+                best_distance = distance;
+            }
+        };
+        for &point in a.points() {
+            for &point_b in b.points() {
+                consider(point, point_b);
+            }
+            for (start, end) in b.edges() {
+                consider(point, closest_on_line(point, start, end));
+            }
+        }
+        for &point in b.points() {
+            for (start, end) in a.edges() {
+                consider(closest_on_line(point, start, end), point);
+            }
+        }
+    }
+    best
+}
+
+// The point on the line between start and end that's closest to point.
+fn closest_on_line(point: Vec2, start: Vec2, end: Vec2) -> Vec2 {
+    let along = end - start;
+    let length = along.magnitude2();
+    if length == 0.0 {
+        return start;
+    }
+    let t = ((point - start).dot(along) / length).max(0.0).min(1.0);
+    start + along * t
+}
+
+// Not part of the original code, which only checked for overlaps once per
+// frame. A fast enough ball could be on one side of a brick in one frame and
+// on the other side in the next, without ever overlapping it. Instead we
+// sweep the ball along its path and find the first thing it touches.
+
+// Where a sweep first touched something.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    // How far along the path, from 0 (the start) to 1 (the end).
+    pub time: f32,
+    // Points out of whatever was hit, towards the circle. Unit length.
+    pub normal: Vec2,
+}
+
+// Sweep a circle from center to center + motion, and find when it first
+// touches the box. The box position is its top-left corner.
+//
+// A circle touching a box is the same thing as the circle's center touching
+// the box grown by the radius, with rounded corners. So this is a ray cast
+// against the grown box, with a ray vs circle test if the ray arrives at one
+// of the corners.
+//
+// A circle that already touches or overlaps the box counts as hitting it at
+// time 0, but only if it's moving further in. Otherwise a circle that was just
+// bounced off a box could never get away from it.
+pub fn sweep_circle_aabb(
+    center: Vec2, radius: f32, motion: Vec2, box_pos: Vec2, box_size: Vec2)
+    -> Option<Contact>
+{
+    let box_max = box_pos + box_size;
+
+    let closest = vec2(
+        center.x.max(box_pos.x).min(box_max.x),
+        center.y.max(box_pos.y).min(box_max.y));
+    let difference = center - closest;
+    if difference.magnitude2() <= radius * radius {
+        let normal = if difference.magnitude2() > 1e-12 {
+            difference.normalize()
+        } else {
+            // The center is inside the box. Push it out of the nearest side.
+            inside_normal(center, box_pos, box_max)
+        };
+        return if motion.dot(normal) < 0.0 {
+            Some(Contact { time: 0.0, normal })
+        } else {
+            None
+        };
+    }
+
+    // Ray cast against the box grown by the radius, one axis at a time.
+    let grown_min = box_pos - vec2(radius, radius);
+    let grown_max = box_max + vec2(radius, radius);
+    let mut enter = ::std::f32::NEG_INFINITY;
+    let mut exit = ::std::f32::INFINITY;
+    let mut normal = vec2(0.0, 0.0);
+    for axis in 0..2 {
+        if motion[axis] == 0.0 {
+            if center[axis] < grown_min[axis]
+                || center[axis] > grown_max[axis]
+            {
+                return None;
+            }
+            continue;
+        }
+        let (mut near, mut far) = (
+            (grown_min[axis] - center[axis]) / motion[axis],
+            (grown_max[axis] - center[axis]) / motion[axis]);
+        // Coming in through the min side means the normal points to -axis.
+        let mut side = -1.0;
+        if near > far {
+            ::std::mem::swap(&mut near, &mut far);
+            side = 1.0;
+        }
+        if near > enter {
+            enter = near;
+            normal = vec2(0.0, 0.0);
+            normal[axis] = side;
+        }
+        exit = exit.min(far);
+    }
+    if enter > exit || enter > 1.0 || exit < 0.0 {
+        return None;
+    }
+
+    // enter is only negative if we start inside one of the grown box's
+    // corners, but outside the rounded part (we checked for overlaps above),
+    // and the corner test below deals with that.
+    let time = enter.max(0.0);
+    let hit = center + motion * time;
+    let corner_x = hit.x < box_pos.x || hit.x > box_max.x;
+    let corner_y = hit.y < box_pos.y || hit.y > box_max.y;
+    if corner_x && corner_y {
+        let corner = vec2(
+            if hit.x < box_pos.x { box_pos.x } else { box_max.x },
+            if hit.y < box_pos.y { box_pos.y } else { box_max.y });
+        return sweep_circle_point(center, radius, motion, corner);
+    }
+    Some(Contact { time, normal })
 }
 
-#[derive(Clone)]
-pub enum Direction {
-    Up,
-    Right,
-    Down,
-    Left,
+// Sweep a circle from center to center + motion, and find when it first
+// touches a point.
+pub fn sweep_circle_point(center: Vec2, radius: f32, motion: Vec2, point: Vec2)
+    -> Option<Contact>
+{
+    // Work along the direction of motion rather than solving the quadratic
+    // directly, which loses a lot of precision when the motion is large
+    // compared to the radius.
+    let length = motion.magnitude();
+    if length == 0.0 {
+        return None;
+    }
+    let direction = motion / length;
+    let offset = point - center;
+    // How far along the path we get closest to the point, and how close.
+    let along = offset.dot(direction);
+    let across = direction * along - offset;
+    let squared = radius * radius - across.magnitude2();
+    if squared < 0.0 {
+        return None;
+    }
+    let back = squared.sqrt();
+    let distance = along - back;
+    if distance < 0.0 || distance > length {
+        return None;
+    }
+    let normal = (across - direction * back).normalize();
+    Some(Contact { time: distance / length, normal })
 }
 
-impl Direction {
-    fn vec(&self) -> cgmath::Vector2<f32> {
-        use cgmath::vec2;
+fn inside_normal(center: Vec2, box_min: Vec2, box_max: Vec2) -> Vec2 {
+    let distances = [
+        (center.x - box_min.x, vec2(-1.0, 0.0)),
+        (box_max.x - center.x, vec2(1.0, 0.0)),
+        (center.y - box_min.y, vec2(0.0, -1.0)),
+        (box_max.y - center.y, vec2(0.0, 1.0)),
+    ];
+    let mut best = distances[0];
+    for &candidate in &distances[1..] {
+        if candidate.0 < best.0 {
+            best = candidate;
+        }
+    }
+    best.1
+}
 
-        match *self {
-            Direction::Up => vec2(0.0, 1.0),
-            Direction::Right => vec2(1.0, 0.0),
-            Direction::Down => vec2(0.0, -1.0),
-            Direction::Left => vec2(-1.0, 0.0),
+// The ball can touch several things at once, e.g. two bricks either side of
+// a seam, or a brick and a wall. Bouncing off each of them in turn would flip
+// the velocity back and forth, so instead we bounce off all of them together.
+//
+// Normals that point the same way (a row of bricks makes one flat surface)
+// only count once. The rest are used in order of how directly the ball is
+// heading into them, and each one only reverses whatever part of the velocity
+// is still heading into its surface. So the ball comes straight back out of
+// an inside corner, but never gets pushed back into anything it touched.
+pub fn bounce(velocity: Vec2, normals: &[Vec2]) -> Vec2 {
+    // Normals closer together than this are treated as the same surface.
+    const SAME_SURFACE: f32 = 0.999;
+
+    let mut surfaces: Vec<Vec2> =
+        Vec::with_capacity(normals.len());
+    for &normal in normals {
+        if !surfaces.iter().any(|s| s.dot(normal) > SAME_SURFACE) {
+            surfaces.push(normal);
+        }
+    }
+    surfaces.sort_by(|a, b| {
+        velocity.dot(*a).partial_cmp(&velocity.dot(*b))
+            .unwrap_or(::std::cmp::Ordering::Equal)
+    });
+
+    let mut velocity = velocity;
+    for normal in surfaces {
+        let into = velocity.dot(normal);
+        if into < 0.0 {
+            velocity -= normal * (2.0 * into);
         }
     }
+    velocity
+}
+
+// A circle hitting the corner of a box gets a diagonal normal. That's right
+// for a box on its own, but bricks are packed edge to edge, and the corner
+// where two of them meet isn't really a corner -- the ball should bounce off
+// the flat surface they make together, not off an edge it could never reach.
+//
+// is_filled says whether there's something at a point. If there is something
+// just past the corner along one axis, that side of the box is covered up,
+// and the normal loses its component along that axis. Normals that aren't
+// from a corner are returned unchanged.
+pub fn flatten_corner<F>(
+    center: Vec2, radius: f32, normal: Vec2, mut is_filled: F)
+    -> Vec2
+    where F: FnMut(Vec2) -> bool
+{
+    // How far from the corner to look.
+    const NUDGE: f32 = 0.5;
+
+    if normal.x == 0.0 || normal.y == 0.0 {
+        return normal;
+    }
+    let corner = center - normal * radius;
+    let step_x = NUDGE * normal.x.signum();
+    let step_y = NUDGE * normal.y.signum();
+    // Something beside the box covers its side, and something above or
+    // below it covers its top or bottom.
+    let side_covered = is_filled(vec2(corner.x + step_x, corner.y - step_y));
+    let end_covered = is_filled(vec2(corner.x - step_x, corner.y + step_y));
+    match (side_covered, end_covered) {
+        (true, false) => vec2(0.0, normal.y.signum()),
+        (false, true) => vec2(normal.x.signum(), 0.0),
+        // Either it really is a corner, or the ball has somehow got into a
+        // gap that's too small for it. Either way the diagonal is the best
+        // we can do.
+        _ => normal.normalize(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn close_vec(a: Vec2, b: Vec2) -> bool {
+        close(a.x, b.x) && close(a.y, b.y)
+    }
+
+    // A box from (0, 0) to (10, 10).
+    fn sweep_box(center: Vec2, radius: f32, motion: Vec2) -> Option<Contact> {
+        sweep_circle_aabb(
+            center, radius, motion, vec2(0.0, 0.0), vec2(10.0, 10.0))
+    }
+
+    #[test]
+    fn fast_ball_hits_thin_brick() {
+        // The ball moves 25 brick widths in one go, and a brick that's only
+        // 4 pixels tall. Checking for overlaps at the start and end of the
+        // step would never see it.
+        let contact = sweep_circle_aabb(
+            vec2(20.0, 100.0), 5.0, vec2(0.0, -1000.0),
+            vec2(0.0, 40.0), vec2(40.0, 4.0))
+            .unwrap();
+        assert!(close(contact.time, (100.0 - 44.0 - 5.0) / 1000.0));
+        assert!(close_vec(contact.normal, vec2(0.0, 1.0)));
+
+        // The same going sideways, into the brick's end.
+        let contact = sweep_circle_aabb(
+            vec2(-200.0, 42.0), 5.0, vec2(1000.0, 0.0),
+            vec2(0.0, 40.0), vec2(40.0, 4.0))
+            .unwrap();
+        assert!(close(contact.time, (200.0 - 5.0) / 1000.0));
+        assert!(close_vec(contact.normal, vec2(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn fast_ball_stops_at_the_first_brick() {
+        let motion = vec2(0.0, -1000.0);
+        let near = sweep_circle_aabb(
+            vec2(20.0, 100.0), 5.0, motion, vec2(0.0, 40.0), vec2(40.0, 4.0))
+            .unwrap();
+        let far = sweep_circle_aabb(
+            vec2(20.0, 100.0), 5.0, motion, vec2(0.0, -400.0), vec2(40.0, 4.0))
+            .unwrap();
+        assert!(near.time < far.time);
+    }
+
+    #[test]
+    fn glancing_corner_hit() {
+        // Skimming along just above the top of the box. The circle's edge
+        // catches the top-left corner, so the normal points from the corner
+        // to the circle's center.
+        let contact = sweep_box(vec2(-10.0, -1.5), 2.0, vec2(40.0, 0.0))
+            .unwrap();
+        let x = -(2.0f32 * 2.0 - 1.5 * 1.5).sqrt();
+        assert!(close(contact.time, (x + 10.0) / 40.0));
+        assert!(close_vec(contact.normal, vec2(x, -1.5) / 2.0));
+    }
+
+    #[test]
+    fn corner_near_miss() {
+        // This path cuts across the corner of the box grown by the radius,
+        // but never comes within the radius of the box's actual corner.
+        assert_eq!(
+            sweep_box(vec2(-6.0, 2.8), 2.0, vec2(8.8, -8.8)), None);
+        // Slightly closer, and it does.
+        assert!(sweep_box(vec2(-6.0, 3.5), 2.0, vec2(8.8, -8.8)).is_some());
+    }
+
+    #[test]
+    fn starting_inside_the_box() {
+        // Nearest the left side, so that's the way out.
+        assert_eq!(
+            sweep_box(vec2(1.0, 5.0), 2.0, vec2(5.0, 0.0)),
+            Some(Contact { time: 0.0, normal: vec2(-1.0, 0.0) }));
+        assert_eq!(sweep_box(vec2(1.0, 5.0), 2.0, vec2(-5.0, 0.0)), None);
+    }
+
+    #[test]
+    fn starting_overlapping_the_box() {
+        // The center is outside, but the circle overlaps the left side.
+        assert_eq!(
+            sweep_box(vec2(-1.0, 5.0), 2.0, vec2(5.0, 0.0)),
+            Some(Contact { time: 0.0, normal: vec2(-1.0, 0.0) }));
+        assert_eq!(sweep_box(vec2(-1.0, 5.0), 2.0, vec2(-5.0, 0.0)), None);
+        // Moving along the side doesn't count as moving in.
+        assert_eq!(sweep_box(vec2(-1.0, 5.0), 2.0, vec2(0.0, 5.0)), None);
+    }
+
+    #[test]
+    fn starting_touching_the_box() {
+        // Exactly touching, e.g. straight after bouncing off it.
+        assert_eq!(sweep_box(vec2(-2.0, 5.0), 2.0, vec2(-5.0, 0.0)), None);
+        assert_eq!(
+            sweep_box(vec2(-2.0, 5.0), 2.0, vec2(5.0, 0.0)),
+            Some(Contact { time: 0.0, normal: vec2(-1.0, 0.0) }));
+    }
+
+    #[test]
+    fn no_motion_on_one_axis() {
+        // Straight down, to the side of the box.
+        assert_eq!(sweep_box(vec2(20.0, -10.0), 2.0, vec2(0.0, 50.0)), None);
+        // Straight down onto the top.
+        let contact = sweep_box(vec2(5.0, -10.0), 2.0, vec2(0.0, 50.0))
+            .unwrap();
+        assert!(close(contact.time, 8.0 / 50.0));
+        assert!(close_vec(contact.normal, vec2(0.0, -1.0)));
+        // Straight down past the top-right corner, near enough to clip it.
+        let contact = sweep_box(vec2(11.5, -10.0), 2.0, vec2(0.0, 50.0))
+            .unwrap();
+        let y = -(2.0f32 * 2.0 - 1.5 * 1.5).sqrt();
+        assert!(close(contact.time, (y + 10.0) / 50.0));
+        assert!(close_vec(contact.normal, vec2(1.5, y) / 2.0));
+    }
+
+    #[test]
+    fn no_motion_at_all() {
+        assert_eq!(sweep_box(vec2(-5.0, 5.0), 2.0, vec2(0.0, 0.0)), None);
+        // Inside the grown box's corner, but not touching the real one.
+        assert_eq!(sweep_box(vec2(-1.8, -1.8), 2.0, vec2(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn too_short_to_reach() {
+        assert_eq!(sweep_box(vec2(-10.0, 5.0), 2.0, vec2(7.9, 0.0)), None);
+        assert!(sweep_box(vec2(-10.0, 5.0), 2.0, vec2(8.1, 0.0)).is_some());
+    }
+
+    #[test]
+    fn circle_point_sweeps() {
+        let point = vec2(0.0, 0.0);
+        // Head on.
+        let contact = sweep_circle_point(
+            vec2(-10.0, 0.0), 2.0, vec2(20.0, 0.0), point).unwrap();
+        assert!(close(contact.time, 8.0 / 20.0));
+        assert!(close_vec(contact.normal, vec2(-1.0, 0.0)));
+        // Passing by too far away.
+        assert_eq!(
+            sweep_circle_point(vec2(-10.0, 3.0), 2.0, vec2(20.0, 0.0), point),
+            None);
+        // Already past it.
+        assert_eq!(
+            sweep_circle_point(vec2(10.0, 0.0), 2.0, vec2(20.0, 0.0), point),
+            None);
+        // A huge step still gives a sensible answer.
+        let contact = sweep_circle_point(
+            vec2(-10.0, 1.0), 2.0, vec2(1.0e6, 0.0), point).unwrap();
+        assert!(contact.normal.x < 0.0 && contact.normal.y > 0.0);
+        assert!(close(contact.normal.magnitude(), 1.0));
+    }
+
+    #[test]
+    fn bounce_off_one_surface() {
+        assert_eq!(
+            bounce(vec2(3.0, -4.0), &[vec2(0.0, 1.0)]), vec2(3.0, 4.0));
+        // Already heading away from it.
+        assert_eq!(
+            bounce(vec2(3.0, 4.0), &[vec2(0.0, 1.0)]), vec2(3.0, 4.0));
+        assert_eq!(bounce(vec2(3.0, -4.0), &[]), vec2(3.0, -4.0));
+    }
+
+    #[test]
+    fn seam_bounces_once() {
+        // Two bricks side by side both give the same normal. Bouncing off
+        // each in turn would send the ball straight back into them.
+        let up = vec2(0.0, 1.0);
+        assert_eq!(bounce(vec2(3.0, -4.0), &[up, up]), vec2(3.0, 4.0));
+        // Near enough the same counts too.
+        let almost = vec2(0.01, 1.0).normalize();
+        let velocity = bounce(vec2(3.0, -4.0), &[up, almost]);
+        assert!(velocity.y > 0.0);
+        assert!(close(velocity.magnitude(), 5.0));
+    }
+
+    #[test]
+    fn inside_corner_reverses_both() {
+        let normals = [vec2(1.0, 0.0), vec2(0.0, 1.0)];
+        assert_eq!(bounce(vec2(-3.0, -4.0), &normals), vec2(3.0, 4.0));
+        let normals = [vec2(0.0, 1.0), vec2(1.0, 0.0)];
+        assert_eq!(bounce(vec2(-3.0, -4.0), &normals), vec2(3.0, 4.0));
+    }
+
+    #[test]
+    fn bounce_never_pushes_back_in() {
+        // Touching a wall on the left while heading right, away from it.
+        let normals = [vec2(1.0, 0.0), vec2(0.0, 1.0)];
+        assert_eq!(bounce(vec2(3.0, -4.0), &normals), vec2(3.0, 4.0));
+        // A corner normal and a face normal. Whatever order they're used
+        // in, the ball ends up leaving both.
+        let corner = vec2(-1.0, 1.0).normalize();
+        let normals = [corner, vec2(0.0, 1.0)];
+        let velocity = bounce(vec2(1.0, -4.0), &normals);
+        assert!(velocity.dot(corner) >= 0.0);
+        assert!(velocity.y >= 0.0);
+    }
+
+    // Whether a point is inside any of the boxes, given as (min, max).
+    fn filled(boxes: &[(Vec2, Vec2)], point: Vec2) -> bool {
+        boxes.iter().any(|&(min, max)| {
+            point.x > min.x && point.x < max.x
+                && point.y > min.y && point.y < max.y
+        })
+    }
+
+    #[test]
+    fn corner_beside_a_neighbour_is_flattened() {
+        // The ball hits the bottom-left corner of the brick on the right,
+        // where it meets the brick on the left. That's a flat surface, so
+        // it should bounce straight down.
+        let boxes = [
+            (vec2(0.0, 0.0), vec2(10.0, 10.0)),
+            (vec2(10.0, 0.0), vec2(20.0, 10.0)),
+        ];
+        let normal = vec2(-0.6, 0.8);
+        let center = vec2(10.0, 10.0) + normal * 5.0;
+        assert_eq!(
+            flatten_corner(center, 5.0, normal, |p| filled(&boxes, p)),
+            vec2(0.0, 1.0));
+    }
+
+    #[test]
+    fn corner_below_a_neighbour_is_flattened() {
+        // Bricks stacked on top of each other make a flat side.
+        let boxes = [
+            (vec2(0.0, 0.0), vec2(10.0, 10.0)),
+            (vec2(0.0, 10.0), vec2(10.0, 20.0)),
+        ];
+        let normal = vec2(-0.6, -0.8);
+        let center = vec2(0.0, 10.0) + normal * 5.0;
+        assert_eq!(
+            flatten_corner(center, 5.0, normal, |p| filled(&boxes, p)),
+            vec2(-1.0, 0.0));
+    }
+
+    #[test]
+    fn real_corners_stay_diagonal() {
+        let boxes = [(vec2(0.0, 0.0), vec2(10.0, 10.0))];
+        let normal = vec2(-0.6, 0.8);
+        let center = vec2(0.0, 10.0) + normal * 5.0;
+        assert!(close_vec(
+            flatten_corner(center, 5.0, normal, |p| filled(&boxes, p)),
+            normal));
+        // Covered on both sides means the ball is somewhere it shouldn't
+        // be, so leave it alone.
+        assert!(close_vec(
+            flatten_corner(center, 5.0, normal, |_| true), normal));
+    }
+
+    #[test]
+    fn face_normals_are_unchanged() {
+        let normal = vec2(0.0, 1.0);
+        assert_eq!(
+            flatten_corner(vec2(5.0, 15.0), 5.0, normal, |_| true), normal);
+    }
+
+    // One of each kind of shape, centered at (x, 0), along with how far it
+    // reaches either side of x.
+    fn shapes(x: f32) -> Vec<(Shape, f32)> {
+        vec![
+            (Aabb { min: vec2(x - 10.0, -5.0), max: vec2(x + 10.0, 5.0) }
+             .into(), 10.0),
+            (Circle { center: vec2(x, 0.0), radius: 5.0 }.into(), 5.0),
+            // Turned on its end, so it's 10 wide rather than 20.
+            (OrientedRect {
+                center: vec2(x, 0.0),
+                half_size: vec2(10.0, 5.0),
+                angle: ::std::f32::consts::FRAC_PI_2,
+            }.into(), 5.0),
+            (Capsule {
+                start: vec2(x, -10.0),
+                end: vec2(x, 10.0),
+                radius: 3.0,
+            }.into(), 3.0),
+        ]
+    }
 
-    pub fn nearest_from(target: &cgmath::Vector2<f32>) -> Self {
-        use cgmath::InnerSpace;
+    #[test]
+    fn every_pair_of_shapes() {
+        for (i, &(a, a_reach)) in shapes(0.0).iter().enumerate() {
+            for j in 0..4 {
+                // Slide b in from the right. Once they're touching, a has
+                // to go left to get out.
+                let touching = a_reach + shapes(0.0)[j].1;
+                let b = |gap: f32| shapes(touching + gap)[j].0;
+                let left = vec2(-1.0, 0.0);
 
-        const COMPASS: [Direction; 4] = [
-            Direction::Up, Direction::Right, Direction::Down, Direction::Left];
-        let mut max = 0.0;
-        let mut best = &Direction::Up;
-        let target = target.clone().normalize();
+                assert_eq!(overlap(&a, &b(1.0)), None, "{} {}", i, j);
 
-        for dir in COMPASS.iter() {
-            let dot_product = target.dot(dir.vec());
-            if dot_product > max {
-                max = dot_product;
-                best = dir;
+                let touch = overlap(&a, &b(0.0))
+                    .unwrap_or_else(|| panic!("{} {} touching", i, j));
+                assert!(close(touch.depth, 0.0), "{} {} {:?}", i, j, touch);
+                assert!(close_vec(touch.normal, left),
+                        "{} {} {:?}", i, j, touch);
+
+                let deep = overlap(&a, &b(-2.0))
+                    .unwrap_or_else(|| panic!("{} {} overlapping", i, j));
+                assert!(close(deep.depth, 2.0), "{} {} {:?}", i, j, deep);
+                assert!(close_vec(deep.normal, left),
+                        "{} {} {:?}", i, j, deep);
+
+                // The other way round, b has to go right by as much.
+                let back = overlap(&b(-2.0), &a).unwrap();
+                assert!(close(back.depth, 2.0), "{} {} {:?}", i, j, back);
+                assert!(close_vec(back.normal, -left),
+                        "{} {} {:?}", i, j, back);
             }
         }
-        best.clone()
+    }
+
+    #[test]
+    fn circles_with_the_same_center() {
+        let a = Circle { center: vec2(1.0, 2.0), radius: 3.0 }.into();
+        let b = Circle { center: vec2(1.0, 2.0), radius: 4.0 }.into();
+        let overlap = overlap(&a, &b).unwrap();
+        assert_eq!(overlap.normal, vec2(0.0, -1.0));
+        assert!(close(overlap.depth, 7.0));
+    }
+
+    #[test]
+    fn circle_center_inside_box() {
+        // The cores overlap, so the circle has to go all the way out of the
+        // nearest side and then its radius as well.
+        let circle = Circle { center: vec2(8.0, 0.0), radius: 5.0 }.into();
+        let aabb = Aabb { min: vec2(-10.0, -5.0), max: vec2(10.0, 5.0) }
+            .into();
+        let overlap = overlap(&circle, &aabb).unwrap();
+        assert!(close_vec(overlap.normal, vec2(1.0, 0.0)));
+        assert!(close(overlap.depth, 7.0));
+    }
+
+    #[test]
+    fn turned_rect_corner_into_box() {
+        let aabb = Aabb { min: vec2(-10.0, -5.0), max: vec2(10.0, 5.0) }
+            .into();
+        let reach = 5.0 * 2.0f32.sqrt();
+        let rect = |x: f32| -> Shape {
+            OrientedRect {
+                center: vec2(x, 0.0),
+                half_size: vec2(5.0, 5.0),
+                angle: ::std::f32::consts::FRAC_PI_4,
+            }.into()
+        };
+        assert_eq!(overlap(&aabb, &rect(10.0 + reach + 0.1)), None);
+        let overlap = overlap(&aabb, &rect(10.0 + reach - 1.0)).unwrap();
+        assert!(close_vec(overlap.normal, vec2(-1.0, 0.0)));
+        assert!(close(overlap.depth, 1.0));
+    }
+
+    #[test]
+    fn crossed_capsules() {
+        let a = Capsule {
+            start: vec2(-10.0, 0.0), end: vec2(10.0, 0.0), radius: 1.0,
+        }.into();
+        let b = Capsule {
+            start: vec2(0.0, -10.0), end: vec2(0.0, 10.0), radius: 1.0,
+        }.into();
+        let overlap = overlap(&a, &b).unwrap();
+        assert!(close(overlap.depth, 12.0));
+        assert!(close(overlap.normal.magnitude(), 1.0));
+    }
+
+    #[test]
+    fn capsule_past_the_end_of_another() {
+        // In line, but not touching. The axis along the line is what shows
+        // they're apart.
+        let a = Capsule {
+            start: vec2(0.0, 0.0), end: vec2(10.0, 0.0), radius: 1.0,
+        }.into();
+        let b = Capsule {
+            start: vec2(13.0, 0.0), end: vec2(20.0, 0.0), radius: 1.0,
+        }.into();
+        assert_eq!(overlap(&a, &b), None);
+    }
+
+    #[test]
+    fn bounds() {
+        let aabb = Aabb { min: vec2(1.0, 2.0), max: vec2(3.0, 4.0) };
+        assert_eq!(Shape::from(aabb).bounds(), aabb);
+
+        let circle = Circle { center: vec2(1.0, 2.0), radius: 3.0 };
+        assert_eq!(
+            Shape::from(circle).bounds(),
+            Aabb { min: vec2(-2.0, -1.0), max: vec2(4.0, 5.0) });
+
+        let rect = OrientedRect {
+            center: vec2(0.0, 0.0),
+            half_size: vec2(1.0, 1.0),
+            angle: ::std::f32::consts::FRAC_PI_4,
+        };
+        let bounds = Shape::from(rect).bounds();
+        let reach = 2.0f32.sqrt();
+        assert!(close_vec(bounds.min, vec2(-reach, -reach)));
+        assert!(close_vec(bounds.max, vec2(reach, reach)));
+
+        let capsule = Capsule {
+            start: vec2(10.0, 5.0), end: vec2(0.0, 0.0), radius: 2.0,
+        };
+        assert_eq!(
+            Shape::from(capsule).bounds(),
+            Aabb { min: vec2(-2.0, -2.0), max: vec2(12.0, 7.0) });
+    }
+
+    #[test]
+    fn separation_of_cores() {
+        let core = |shape: Shape| Core::from(&shape);
+        let a = core(Aabb { min: vec2(0.0, 0.0), max: vec2(10.0, 10.0) }
+                     .into());
+        let b = core(Aabb { min: vec2(8.0, 1.0), max: vec2(18.0, 9.0) }
+                     .into());
+        let (depth, normal) = separation(&a, &b).unwrap();
+        assert!(close(depth, 2.0));
+        assert!(close_vec(normal, vec2(-1.0, 0.0)));
+
+        let c = core(Aabb { min: vec2(11.0, 0.0), max: vec2(21.0, 10.0) }
+                     .into());
+        assert!(separation(&a, &c).is_none());
+
+        // Two points have no axes to test.
+        let point = core(Circle { center: vec2(0.0, 0.0), radius: 1.0 }
+                         .into());
+        assert!(separation(&point, &point).is_none());
+    }
+
+    #[test]
+    fn closest_points_of_cores() {
+        let core = |shape: Shape| Core::from(&shape);
+        let point = |x: f32, y: f32| {
+            core(Circle { center: vec2(x, y), radius: 1.0 }.into())
+        };
+        let line = core(Capsule {
+            start: vec2(0.0, 0.0), end: vec2(10.0, 0.0), radius: 1.0,
+        }.into());
+
+        // Beside the line, and past its end.
+        assert_eq!(
+            closest_points(&point(5.0, 5.0), &line),
+            (vec2(5.0, 5.0), vec2(5.0, 0.0)));
+        assert_eq!(
+            closest_points(&line, &point(15.0, 5.0)),
+            (vec2(10.0, 0.0), vec2(15.0, 5.0)));
+
+        // A box corner nearest a point.
+        let aabb = core(Aabb { min: vec2(0.0, 0.0), max: vec2(10.0, 10.0) }
+                        .into());
+        assert_eq!(
+            closest_points(&aabb, &point(13.0, 14.0)),
+            (vec2(10.0, 10.0), vec2(13.0, 14.0)));
     }
 }
@@ -0,0 +1,390 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code, which had all of these numbers scattered
+// around as constants. They decide how the game feels, so they're kept
+// together in a TOML file that can be tweaked without recompiling:
+//
+//     [paddle]
+//     size = [100.0, 20.0]
+//     speed = 500.0
+//     strength = 2.0
+//
+//     [ball]
+//     radius = 12.5
+//     velocity = [100.0, -350.0]
+//
+//     [controls]
+//     move_left = ["A", "Left"]
+//     launch = ["Space"]
+//
+// Anything that's left out keeps its default value, which is what the game
+// has always used. The file is watched while the game is running (see
+// ConfigWatcher), so changes show up straight away.
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use cgmath;
+use toml;
+
+use errors::*;
+use input;
+use input::Action;
+
+
+// Where the game looks for its config.
+pub const CONFIG_FILE: &str = "assets/config.toml";
+
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub paddle: PaddleConfig,
+    pub ball: BallConfig,
+    pub controls: ControlsConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaddleConfig {
+    // Width and height, before any powerups.
+    pub size: [f32; 2],
+    // Pixels per second.
+    pub speed: f32,
+    // How much the ball's direction depends on where it hits the paddle.
+    // At 0 it always bounces straight back the way it came.
+    pub strength: f32,
+}
+
+impl Default for PaddleConfig {
+    fn default() -> Self {
+        Self {
+            size: [100.0, 20.0],
+            speed: 500.0,
+            strength: 2.0,
+        }
+    }
+}
+
+impl PaddleConfig {
+    pub fn size(&self) -> cgmath::Vector2<f32> {
+        self.size.into()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BallConfig {
+    pub radius: f32,
+    // Pixels per second, when the ball is launched from the paddle. Levels
+    // can make this faster or slower.
+    pub velocity: [f32; 2],
+}
+
+impl Default for BallConfig {
+    fn default() -> Self {
+        Self {
+            radius: 12.5,
+            velocity: [100.0, -350.0],
+        }
+    }
+}
+
+impl BallConfig {
+    pub fn velocity(&self) -> cgmath::Vector2<f32> {
+        self.velocity.into()
+    }
+}
+
+// The keys bound to each action, by name (see input::Bindings). An action can
+// have as many keys as you like, and holding any of them does the action.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlsConfig {
+    pub move_left: Vec<String>,
+    pub move_right: Vec<String>,
+    pub launch: Vec<String>,
+    pub pause: Vec<String>,
+    pub menu_up: Vec<String>,
+    pub menu_down: Vec<String>,
+    pub confirm: Vec<String>,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        fn keys(names: &[&str]) -> Vec<String> {
+            names.iter().map(|n| n.to_string()).collect()
+        }
+        Self {
+            move_left: keys(&["A", "Left"]),
+            move_right: keys(&["D", "Right"]),
+            launch: keys(&["Space"]),
+            pause: keys(&["P"]),
+            menu_up: keys(&["W", "Up"]),
+            menu_down: keys(&["S", "Down"]),
+            confirm: keys(&["Enter"]),
+        }
+    }
+}
+
+impl ControlsConfig {
+    pub fn keys(&self, action: Action) -> &[String] {
+        match action {
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::Launch => &self.launch,
+            Action::Pause => &self.pause,
+            Action::MenuUp => &self.menu_up,
+            Action::MenuDown => &self.menu_down,
+            Action::Confirm => &self.confirm,
+        }
+    }
+}
+
+impl GameConfig {
+    // A missing file just means the defaults are used.
+    pub fn load<P: AsRef<Path>>(path: &P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut data = String::new();
+        match fs::File::open(path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut data)
+                    .chain_err(|| format!(
+                        "couldn't read config from {}", path.display()))?;
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            },
+            Err(e) => {
+                return Err(e).chain_err(|| format!(
+                    "couldn't open config at {}", path.display()));
+            },
+        }
+
+        Self::parse(&data)
+            .chain_err(|| format!("bad config in {}", path.display()))
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let config: Self = toml::from_str(data)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Catch the values that would break the game, rather than just make it
+    // play strangely.
+    fn validate(&self) -> Result<()> {
+        let size = self.paddle.size;
+        if !(is_positive(size[0]) && is_positive(size[1])) {
+            bail!("paddle.size must be positive, not {:?}", size);
+        }
+        if !(is_positive(self.paddle.speed) || self.paddle.speed == 0.0) {
+            bail!("paddle.speed can't be negative, not {}", self.paddle.speed);
+        }
+        if !self.paddle.strength.is_finite() {
+            bail!("paddle.strength must be a number, not {}",
+                  self.paddle.strength);
+        }
+        if !is_positive(self.ball.radius) {
+            bail!("ball.radius must be positive, not {}", self.ball.radius);
+        }
+        // The ball has to be launched upwards, or it goes straight off the
+        // bottom of the screen.
+        let velocity = self.ball.velocity;
+        if !(velocity[0].is_finite() && is_positive(-velocity[1])) {
+            bail!("ball.velocity must point upwards (negative y), not {:?}",
+                  velocity);
+        }
+        // An action with no keys can never be done, and most of them are
+        // needed to get anywhere at all. A misspelt key would leave an action
+        // with no keys just as surely, so check the names too.
+        for &action in Action::all() {
+            let keys = self.controls.keys(action);
+            if keys.is_empty() {
+                bail!("controls.{} needs at least one key", action.name());
+            }
+            for key in keys {
+                if key.trim().eq_ignore_ascii_case(input::QUIT_KEY) {
+                    bail!("controls.{}: {} always quits, so it can't be bound",
+                          action.name(), input::QUIT_KEY);
+                }
+                if !input::is_key_name(key) {
+                    bail!("controls.{}: unknown key {:?}", action.name(), key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// Notices when the config file changes, so it can be reloaded while the game
+// is running. There's no portable way to be told about changes, so it just
+// checks when the file was last modified.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new<P: AsRef<Path>>(path: &P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified_time(&path);
+        Self {
+            path,
+            modified,
+        }
+    }
+
+    // If the file has changed since last time, load it again. Returns None
+    // if nothing has changed. A file that has been deleted goes back to the
+    // defaults.
+    pub fn poll(&mut self) -> Option<Result<GameConfig>> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(GameConfig::load(&self.path))
+    }
+}
+
+// Not NaN or infinite either.
+fn is_positive(value: f32) -> bool {
+    value > 0.0 && value.is_finite()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn rejects(data: &str, expected: &str) {
+        match GameConfig::parse(data) {
+            Ok(_) => panic!("accepted {:?}", data),
+            Err(e) => {
+                let message = e.to_string();
+                assert!(message.contains(expected),
+                        "{:?} gave {:?}", data, message);
+            },
+        }
+    }
+
+    // A file in a directory of its own, so tests don't trip over each other.
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(
+            format!("breakout-config-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("config.toml")
+    }
+
+    #[test]
+    fn defaults_match_the_original_game() {
+        let config = GameConfig::default();
+        assert_eq!(config.paddle.size(), cgmath::vec2(100.0, 20.0));
+        assert_eq!(config.paddle.speed, 500.0);
+        assert_eq!(config.paddle.strength, 2.0);
+        assert_eq!(config.ball.radius, 12.5);
+        assert_eq!(config.ball.velocity(), cgmath::vec2(100.0, -350.0));
+        // The keys the original game used are still bound.
+        let bound = |action, key: &str| {
+            config.controls.keys(action).iter().any(|k| k == key)
+        };
+        assert!(bound(Action::MoveLeft, "A"));
+        assert!(bound(Action::MoveRight, "D"));
+        assert!(bound(Action::Launch, "Space"));
+        assert!(bound(Action::MenuUp, "W"));
+        assert!(bound(Action::MenuDown, "S"));
+        assert!(bound(Action::Confirm, "Enter"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn empty_file_is_the_defaults() {
+        assert_eq!(GameConfig::parse("").unwrap(), GameConfig::default());
+    }
+
+    #[test]
+    fn anything_left_out_is_the_default() {
+        let config = GameConfig::parse("\
+[ball]
+radius = 20.0
+
+[controls]
+launch = [\"Up\"]
+").unwrap();
+        let defaults = GameConfig::default();
+        assert_eq!(config.ball.radius, 20.0);
+        assert_eq!(config.ball.velocity, defaults.ball.velocity);
+        assert_eq!(config.paddle, defaults.paddle);
+        assert_eq!(config.controls.launch, vec!["Up".to_string()]);
+        assert_eq!(config.controls.confirm, defaults.controls.confirm);
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        rejects("[paddle]\nsize = [-100.0, 20.0]", "paddle.size");
+        rejects("[paddle]\nspeed = -1.0", "paddle.speed");
+        rejects("[paddle]\nstrength = nan", "paddle.strength");
+        rejects("[ball]\nradius = 0.0", "ball.radius");
+        rejects("[ball]\nvelocity = [100.0, 350.0]", "ball.velocity");
+        rejects("[controls]\nlaunch = []", "controls.launch");
+        rejects("[controls]\npause = [\"Escape\"]", "Escape");
+        rejects("[controls]\nconfirm = [\"Return\"]", "unknown key");
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(GameConfig::parse("[ball]\nspeed = 2.0").is_err());
+        assert!(GameConfig::parse("[bat]\nsize = [1.0, 1.0]").is_err());
+    }
+
+    #[test]
+    fn missing_file_is_the_defaults() {
+        let path = temp_file("missing");
+        assert_eq!(GameConfig::load(&path).unwrap(), GameConfig::default());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn watcher_reloads_changes() {
+        let path = temp_file("watcher");
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        fs::write(&path, "[ball]\nradius = 20.0\n").unwrap();
+        let config = watcher.poll().unwrap().unwrap();
+        assert_eq!(config.ball.radius, 20.0);
+        assert!(watcher.poll().is_none());
+
+        // Deleting it goes back to the defaults.
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap(), GameConfig::default());
+        assert!(watcher.poll().is_none());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -15,19 +15,53 @@
 use gfx;
 use glfw;
 use image;
+use rodio;
+use rusttype;
+use toml;
 
 
 error_chain! {
     // Declare that we want to convert these non-error-chain errors into
     // error-chain errors.
     foreign_links {
+        BufferCreationError(gfx::buffer::CreationError);
         CombinedError(gfx::CombinedError);
         CreateProgramError(gfx::shade::core::CreateProgramError);
+        DecoderError(rodio::decoder::DecoderError);
+        FontError(rusttype::Error);
         GlfwInit(glfw::InitError);
         ImageError(image::ImageError);
         Io(::std::io::Error);
         ParseIntError(::std::num::ParseIntError);
         PipelineStateError(gfx::PipelineStateError<String>);
         ProgramError(gfx::shade::ProgramError);
+        TomlError(toml::de::Error);
+    }
+
+    // Our own errors. Line and column numbers count from 1.
+    errors {
+        LevelHeader(file: String, message: String) {
+            description("invalid level header")
+            display("{}: invalid header: {}", file, message)
+        }
+        LevelHeaderSyntax(file: String, line: usize, column: usize,
+                          message: String) {
+            description("invalid level header")
+            display("{}:{}:{}: invalid header: {}", file, line, column, message)
+        }
+        LevelUnknownTile(file: String, line: usize, column: usize, tile: String) {
+            description("unknown tile in level")
+            display("{}:{}:{}: unknown tile {:?}", file, line, column, tile)
+        }
+        LevelRaggedRow(file: String, line: usize, column: usize,
+                       expected: usize, found: usize) {
+            description("level rows are different widths")
+            display("{}:{}:{}: row is {} tiles wide, but the first row is {}",
+                    file, line, column, found, expected)
+        }
+        LevelEmpty(file: String) {
+            description("level has no tiles")
+            display("{}: the level has no tiles", file)
+        }
     }
 }
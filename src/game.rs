@@ -12,172 +12,549 @@
 //
 // The original code was modified by Adrian Chan in order to port it to Rust.
 
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
 
 use cgmath;
-use gfx;
-use glfw;
+use rand;
+use rand::{Rng, SeedableRng};
 
-use collision::Collision;
+use animation::{AnimationClip, PlayMode};
+use audio::AudioManager;
+use brick::Hit;
+use collision;
+use config::GameConfig;
 use errors::*;
 use game_level::GameLevel;
-use game_object;
 use game_object::{BallObject, GameObject};
-use renderer;
-use resource_manager::ResourceManager;
+use high_scores::{HighScore, HighScores};
+use input::{Action, InputState};
+use particle_generator::{ParticleConfig, ParticleGenerator};
+use powerup::{PowerUp, PowerUpKind};
+use score::Score;
 
 
-// Use the colour of the sprite as-in.
-macro_rules! base_colour {
-    () => { cgmath::vec3(1.0, 1.0, 1.0) }
-}
+pub const DEFAULT_LIVES: u32 = 3;
+// The levels that Game::new loads, in the order they're played.
+pub const LEVEL_FILES: &[&str] = &[
+    "assets/levels/one.lvl",
+    "assets/levels/two.lvl",
+    "assets/levels/three.lvl",
+    "assets/levels/four.lvl",
+    "assets/levels/five.lvl",];
+// Any fixed seed will do. It just needs to be the same every time so that
+// games can be reproduced.
+const RNG_SEED: [u32; 4] = [0x193a_6754, 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb];
+// The most times the ball can bounce in one step. It only gets anywhere near
+// this if it's wedged between things.
+const MAX_BOUNCES_PER_STEP: u32 = 8;
+// The multiball powerup won't split the ball if there are already this many.
+const MAX_BALLS: usize = 9;
 
 
-pub struct Game <F: gfx::traits::FactoryExt<R>, R: gfx::Resources> {
+// The game itself is pure simulation -- it knows where everything is and
+// which sprite it should be drawn with, but it doesn't own any graphics
+// resources. Drawing is done by game_renderer::GameRenderer, which treats the
+// game as a read-only view. This means we can create and update a Game
+// without a window or a graphics context.
+pub struct Game {
     height: i32,
     width: i32,
     state: GameState,
-    projection: cgmath::Matrix4<f32>,
-    resources: ResourceManager<F, R>,
-    sprite_renderer: renderer::SpriteRenderer<R>,
-    levels: Vec<GameLevel<R>>,
+    levels: Vec<GameLevel>,
     level: usize,
-    player: GameObject<R>,
-    ball: BallObject<R>,
+    config: GameConfig,
+    player: GameObject,
+    // There's always at least one ball. A life is only lost when the last
+    // one drops off the bottom of the screen.
+    balls: Vec<BallObject>,
+    // The trails that follow the balls.
+    particles: ParticleGenerator,
+    powerups: Vec<PowerUp>,
+    // Somewhere to put the bricks near the ball while it moves, kept around
+    // so that every sweep doesn't have to allocate a new one.
+    nearby_bricks: Vec<usize>,
+    // Played by every brick as it's destroyed.
+    brick_break: Rc<AnimationClip>,
+    // Screen effects caused by the negative powerups.
+    confuse: bool,
+    chaos: bool,
+    // The screen shakes for a short time after hitting a solid brick.
+    shake_time: f32,
+    // Seconds of simulation so far. This drives the screen effects.
+    time: f32,
+    rng: rand::XorShiftRng,
+    lives: u32,
+    starting_lives: u32,
+    score: Score,
+    high_scores: HighScores,
+    // Where the last run ended up in the high score table, if it made it.
+    high_score_rank: Option<usize>,
+    events: Vec<GameEvent>,
+    audio: AudioManager,
+    // One-shot actions (like choosing a menu entry) that have already been
+    // done, and must be released before they can be done again.
+    actions_processed: HashSet<Action>,
+    // While paused, nothing moves until the player unpauses.
+    paused: bool,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GameState {
     Active,
     Menu,
     Win,
+    Lose,
+}
+
+// Effects applied to the whole screen by the post processor.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScreenEffects {
+    pub shake: bool,
+    pub confuse: bool,
+    pub chaos: bool,
+}
+
+// Things that happened during an update that the host might want to react
+// to. They are queued up until the host collects them with drain_events().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    // The level with this number (counting from 1) has been cleared.
+    LevelCleared(usize),
+    // The last level has been cleared.
+    GameWon,
+    // The run that just ended made it into the high score table, at this
+    // place (counting from 1).
+    HighScore(usize),
 }
 
-impl<F: gfx::traits::FactoryExt<R> + Clone, R: gfx::Resources> Game<F, R> {
-    pub fn new(
-        fb_width: i32, fb_height: i32,
-        mut factory: F, fb: renderer::RenderTargetView<R>
-        )-> Result<Self>
+// Something the ball can bounce off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Obstacle {
+    Wall,
+    // An index into the current level's bricks.
+    Brick(usize),
+    Paddle,
+}
+
+impl Game {
+    pub fn new(fb_width: i32, fb_height: i32, config: GameConfig)
+        -> Result<Self>
     {
-        let mut resources = ResourceManager::new(factory.clone());
-        resources.load_shader(
-            &"assets/shaders/sprite.vs", &"assets/shaders/sprite.fs", None,
-            "sprite".into())?;
-        // Textures.
-        resources.load_texture(
-            &"assets/textures/awesomeface.png",
-            "face".into())?;
-        resources.load_texture(
-            &"assets/textures/background.jpg",
-            "background".into())?;
-        resources.load_texture(
-            &"assets/textures/block.png",
-            "block".into())?;
-        resources.load_texture(
-            &"assets/textures/block_solid.png",
-            "block_solid".into())?;
-        resources.load_texture(
-            &"assets/textures/paddle.png",
-            "paddle".into())?;
-
-        // left, right, bottom, top, near, far.
-        // Note that bottom and top are "backwards", with y increasing down
-        // the screen.
-        let projection = cgmath::ortho(
-            0.0, fb_width as f32,
-            fb_height as f32, 0.0,
-            -1.0, 1.0);
-
-        let sprite_renderer = renderer::SpriteRenderer::new(
-            resources.shader("sprite").unwrap(),
-            &projection,
-            &mut factory,
-            fb.clone())?;
-
-        let level_data = [
-            "assets/levels/one.lvl",
-            "assets/levels/two.lvl",
-            "assets/levels/three.lvl",
-            "assets/levels/four.lvl",];
-        let mut levels = Vec::with_capacity(level_data.len());
-        for level in level_data.iter() {
+        let mut levels = Vec::with_capacity(LEVEL_FILES.len());
+        for level in LEVEL_FILES.iter() {
             let lvl = GameLevel::new(
-                level, fb_width as u32, (fb_height / 2) as u32, &resources)?;
+                level, fb_width as u32, (fb_height / 2) as u32)?;
             levels.push(lvl);
         }
 
-        let player_size = game_object::initial_player_size();
+        Self::with_levels(fb_width, fb_height, levels, config)
+    }
+
+    pub fn with_levels(
+        fb_width: i32, fb_height: i32, levels: Vec<GameLevel>,
+        config: GameConfig)
+        -> Result<Self>
+    {
+        if levels.is_empty() {
+            bail!("the game needs at least one level");
+        }
+
+        let player_size = config.paddle.size();
         let player_pos = cgmath::vec2(
             (fb_width as f32 / 2.0) - (player_size.x / 2.0),
             fb_height as f32 - player_size.y);
         let player = GameObject::new(
-            player_pos, player_size,
-            resources.texture("paddle").unwrap(),
-            base_colour!());
+            player_pos, player_size, "paddle", base_colour!());
 
-        let ball_radius = <BallObject<R>>::initial_radius();
-        let initial_ball_velocity = <BallObject<R>>::initial_velocity();
+        let ball_radius = config.ball.radius;
+        let initial_ball_velocity = config.ball.velocity();
         let ball_pos = player_pos + cgmath::vec2(
             player_size.x / 2.0 - ball_radius,
             -ball_radius * 2.0);
-        let ball = BallObject::new(
+        let mut ball = BallObject::new(
             ball_pos, ball_radius, initial_ball_velocity,
-            resources.texture("face").unwrap(),
-            base_colour!());
+            "face", base_colour!());
+        ball.play(Rc::new(AnimationClip::from_sheet(
+            "ball_spin", 8, 0.06, PlayMode::Loop)));
+
+        let mut rng = rand::XorShiftRng::from_seed(RNG_SEED);
+        let particles = ParticleGenerator::new(
+            ParticleConfig {
+                offset: cgmath::vec2(ball_radius / 2.0, ball_radius / 2.0),
+                ..ParticleConfig::default()
+            },
+            rng.gen());
 
         Ok(Self {
             height: fb_height,
             width: fb_width,
-            state: GameState::Active,
-            projection,
-            resources,
-            sprite_renderer,
+            state: GameState::Menu,
             levels,
             level: 1,
+            config,
             player,
-            ball,
+            balls: vec![ball],
+            particles,
+            powerups: Vec::with_capacity(10),
+            nearby_bricks: Vec::with_capacity(16),
+            brick_break: Rc::new(AnimationClip::from_sheet(
+                "brick_break", 4, 0.05, PlayMode::Once)),
+            confuse: false,
+            chaos: false,
+            shake_time: 0.0,
+            time: 0.0,
+            rng,
+            lives: DEFAULT_LIVES,
+            starting_lives: DEFAULT_LIVES,
+            score: Score::new(),
+            high_scores: HighScores::in_memory(),
+            high_score_rank: None,
+            events: Vec::with_capacity(10),
+            audio: AudioManager::null(),
+            actions_processed: HashSet::with_capacity(10),
+            paused: false,
         })
     }
 
-    pub fn process_input(
-        &mut self, delta_time: f32, keys: &HashMap<glfw::Key, bool>)
-    {
-        if let GameState::Active = self.state {
-            const PLAYER_VELOCITY: f32 = 500.0;
-            let velocity = PLAYER_VELOCITY * delta_time;
-
-            // Movement.
-            let old_x = self.player.position.x;
-            if *keys.get(&glfw::Key::A).unwrap_or(&false) {
-                self.player.position.x -= velocity;
-                if self.player.position.x < 0.0 {
-                    self.player.position.x = 0.0;
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    // The level number, counting from 1.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn particles(&self) -> &ParticleGenerator {
+        &self.particles
+    }
+
+    // Every sprite that anything in the game could be drawn with, so the
+    // renderer can make sure it has them all before the game starts.
+    pub fn sprite_names(&self) -> Vec<String> {
+        let mut names = self.player.sprite_names();
+        for ball in self.balls.iter() {
+            names.extend(ball.object().sprite_names());
+        }
+        for level in self.levels.iter() {
+            for brick in level.bricks_iter() {
+                names.extend(brick.object().sprite_names());
+            }
+        }
+        names.extend(self.brick_break.sprite_names());
+        names.extend(PowerUp::sprite_names());
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn powerups_iter(&self) -> ::std::slice::Iter<PowerUp> {
+        self.powerups.iter()
+    }
+
+    // Winning is celebrated with a bit of chaos.
+    pub fn screen_effects(&self) -> ScreenEffects {
+        ScreenEffects {
+            shake: self.shake_time > 0.0,
+            confuse: self.confuse,
+            chaos: self.chaos || self.state == GameState::Win,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn starting_lives(&self) -> u32 {
+        self.starting_lives
+    }
+
+    // How many lives the player gets at the start of each game. This takes
+    // effect the next time a game is started from the menu.
+    pub fn set_starting_lives(&mut self, lives: u32) {
+        self.starting_lives = ::std::cmp::max(lives, 1);
+    }
+
+    // The score is kept after the game ends, so it can be shown on the win
+    // and lose screens. It's reset when a new game starts.
+    pub fn score(&self) -> &Score {
+        &self.score
+    }
+
+    // The game only keeps the table up to date. Saving it is up to the host,
+    // which is told about new entries with GameEvent::HighScore.
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = high_scores;
+    }
+
+    pub fn high_scores(&self) -> &HighScores {
+        &self.high_scores
+    }
+
+    pub fn high_score_rank(&self) -> Option<usize> {
+        self.high_score_rank
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    // Takes effect straight away, even in the middle of a game. The paddle
+    // keeps any extra width from powerups, and balls that are already moving
+    // keep going at the same speed. Balls waiting on the paddle pick up the
+    // new launch velocity, so they go off with it when they're launched.
+    pub fn set_config(&mut self, config: GameConfig) {
+        use self::cgmath::vec2;
+
+        let old_size = self.config.paddle.size();
+        let new_size = config.paddle.size();
+        let width = self.width as f32;
+        let height = self.height as f32;
+        let player = &mut self.player;
+        let center = player.position.x + player.size.x / 2.0;
+        player.size = vec2(
+            (player.size.x - old_size.x + new_size.x).min(width),
+            new_size.y);
+        player.position = vec2(
+            (center - player.size.x / 2.0).max(0.0).min(width - player.size.x),
+            height - player.size.y);
+        player.store_position();
+
+        let radius = config.ball.radius;
+        let velocity = config.ball.velocity()
+            * self.levels[self.level - 1].info().ball_speed;
+        for ball in self.balls.iter_mut() {
+            ball.set_radius(radius);
+            if ball.is_stuck() {
+                // Keep it sitting on top of the paddle.
+                let mut position = ball.object().position;
+                position.y = player.position.y - radius * 2.0;
+                ball.translate(position - ball.object().position);
+                ball.set_velocity(velocity);
+            }
+            ball.store_position();
+        }
+        self.particles.set_offset(vec2(radius / 2.0, radius / 2.0));
+
+        self.config = config;
+    }
+
+    // Sounds are played through this. The default doesn't play anything.
+    pub fn set_audio(&mut self, audio: AudioManager) {
+        self.audio = audio;
+    }
+
+    // Take all of the events that have happened since the last call.
+    pub fn drain_events(&mut self) -> ::std::vec::Drain<GameEvent> {
+        self.events.drain(..)
+    }
+
+    pub fn current_level(&self) -> &GameLevel {
+        &self.levels[self.level - 1]
+    }
+
+    pub fn player(&self) -> &GameObject {
+        &self.player
+    }
+
+    pub fn balls(&self) -> &[BallObject] {
+        &self.balls
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // One whole update: input, then simulation. Given the same delta time
+    // and the same input, a game always ends up in exactly the same state, so
+    // hosts should call this with a fixed delta time (see
+    // timestep::FixedTimestep).
+    pub fn step(&mut self, delta_time: f32, input: &InputState) {
+        self.store_positions();
+        self.process_input(delta_time, input);
+        self.update(delta_time);
+    }
+
+    pub fn process_input(&mut self, delta_time: f32, input: &InputState) {
+        // Once an action is released it can be done again.
+        self.actions_processed.retain(|&a| input.is_down(a));
+
+        match self.state {
+            GameState::Menu => self.process_menu_input(input),
+            GameState::Active => self.process_active_input(delta_time, input),
+            GameState::Win | GameState::Lose => self.process_end_input(input),
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        if self.shake_time > 0.0 {
+            self.shake_time -= delta_time;
+        }
+
+        if self.state == GameState::Active && !self.paused {
+            for i in 0..self.balls.len() {
+                self.move_ball(i, delta_time);
+            }
+            self.do_collisions();
+            self.particles.update(
+                delta_time, self.balls.iter().map(|b| b.object()));
+            self.update_powerups(delta_time);
+            self.update_animations(delta_time);
+            // Lost balls are gone for good, unless they were the last one.
+            let height = self.height as f32;
+            if self.balls.iter().all(|b| b.is_below(height)) {
+                self.lives -= 1;
+                if self.lives == 0 {
+                    self.record_run();
+                    self.reset_level();
+                    self.state = GameState::Lose;
                 }
+                self.reset_player();
             }
-            if *keys.get(&glfw::Key::D).unwrap_or(&false) {
-                self.player.position.x += velocity;
-                if self.player.position.x + self.player.size.x > self.width as f32 {
-                    self.player.position.x = self.width as f32 - self.player.size.x;
+            else {
+                self.balls.retain(|b| !b.is_below(height));
+                if self.levels[self.level - 1].is_completed() {
+                    self.advance_level();
                 }
             }
+        }
+    }
+
+    // Remember where everything that moves is, so the renderer can blend
+    // between the last step and this one.
+    fn store_positions(&mut self) {
+        self.player.store_position();
+        for ball in self.balls.iter_mut() {
+            ball.store_position();
+        }
+        for powerup in self.powerups.iter_mut() {
+            powerup.store_position();
+        }
+    }
+
+    // Move on to the next level, or to the Win state if that was the last.
+    fn advance_level(&mut self) {
+        self.score.level_cleared(self.lives);
+        self.events.push(GameEvent::LevelCleared(self.level));
+        // Leave the cleared level ready to be played again.
+        self.reset_level();
+        if self.level < self.levels.len() {
+            self.level += 1;
+        }
+        else {
+            self.events.push(GameEvent::GameWon);
+            self.record_run();
+            self.state = GameState::Win;
+        }
+        self.reset_player();
+    }
+
+    // Put the run that just ended into the high score table, if it's good
+    // enough. This needs to happen before moving off the level it ended on.
+    fn record_run(&mut self) {
+        let entry = HighScore::new(
+            &HighScores::player_name(), self.score.points(), self.level);
+        self.high_score_rank = self.high_scores.insert(entry);
+        if let Some(rank) = self.high_score_rank {
+            self.events.push(GameEvent::HighScore(rank));
+        }
+    }
+
+    fn process_menu_input(&mut self, input: &InputState) {
+        let num_levels = self.levels.len();
+
+        if self.pressed_once(input, Action::Confirm) {
+            self.lives = self.starting_lives;
+            self.score.reset();
+            self.high_score_rank = None;
+            self.paused = false;
+            self.state = GameState::Active;
+            // Levels can change the speed of the ball, so it needs to be
+            // set up for whichever level was picked.
+            self.reset_player();
+        }
+        if self.pressed_once(input, Action::MenuUp) {
+            self.level = self.level % num_levels + 1;
+        }
+        if self.pressed_once(input, Action::MenuDown) {
+            self.level = if self.level > 1 { self.level - 1 } else { num_levels };
+        }
+    }
 
-            if self.ball.is_stuck() {
-                let dx = self.player.position.x - old_x;
-                self.ball.move_with_paddle(dx);
+    fn process_active_input(&mut self, delta_time: f32, input: &InputState) {
+        if self.pressed_once(input, Action::Pause) {
+            self.paused = !self.paused;
+        }
+        if self.paused {
+            return;
+        }
+
+        let velocity = self.config.paddle.speed * delta_time;
+
+        // Movement.
+        let old_x = self.player.position.x;
+        if input.is_down(Action::MoveLeft) {
+            self.player.position.x -= velocity;
+            if self.player.position.x < 0.0 {
+                self.player.position.x = 0.0;
             }
+        }
+        if input.is_down(Action::MoveRight) {
+            self.player.position.x += velocity;
+            if self.player.position.x + self.player.size.x > self.width as f32 {
+                self.player.position.x = self.width as f32 - self.player.size.x;
+            }
+        }
+
+        let dx = self.player.position.x - old_x;
+        for ball in self.balls.iter_mut().filter(|b| b.is_stuck()) {
+            ball.move_with_paddle(dx);
+        }
 
-            // Release ball.
-            if *keys.get(&glfw::Key::Space).unwrap_or(&false) {
-                self.ball.release();
+        // Release the balls.
+        if input.is_down(Action::Launch) {
+            for ball in self.balls.iter_mut() {
+                ball.release();
             }
         }
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        self.ball.do_move(delta_time, self.width as f32);
-        self.do_collisions();
-        if self.ball.is_below(self.height as f32) {
-            self.reset_level();
-            self.reset_player();
+    // Used for both winning and losing -- either way the player goes back to
+    // the menu when they're ready.
+    fn process_end_input(&mut self, input: &InputState) {
+        if self.pressed_once(input, Action::Confirm) {
+            self.state = GameState::Menu;
+        }
+    }
+
+    // True if the action is held and hasn't already been done. Marks the
+    // action as processed, so holding it doesn't repeat it every frame.
+    fn pressed_once(&mut self, input: &InputState, action: Action) -> bool {
+        if input.is_down(action) && !self.actions_processed.contains(&action)
+        {
+            self.actions_processed.insert(action);
+            true
+        }
+        else {
+            false
         }
     }
 
@@ -188,60 +565,800 @@ impl<F: gfx::traits::FactoryExt<R> + Clone, R: gfx::Resources> Game<F, R> {
     fn reset_player(&mut self) {
         use self::cgmath::vec2;
 
-        let player_size = game_object::initial_player_size();
+        let player_size = self.config.paddle.size();
         self.player.size = player_size;
         self.player.position = vec2(
             (self.width as f32 / 2.0) - (player_size.x / 2.0),
             self.height as f32 - player_size.y);
+        self.player.store_position();
 
-        let ball_radius = <BallObject<R>>::initial_radius();
-        self.ball.reset(
+        // Back down to one ball.
+        self.balls.truncate(1);
+        let ball_radius = self.config.ball.radius;
+        let ball_speed = self.current_level().info().ball_speed;
+        self.balls[0].reset(
             self.player.position + vec2(
                 (player_size.x / 2.0) - ball_radius, -(ball_radius * 2.0)),
-            <BallObject<R>>::initial_velocity());
+            self.config.ball.velocity() * ball_speed);
+
+        // Losing the ball also loses any powerups, and the combo.
+        self.score.end_combo();
+        self.powerups.clear();
+        self.confuse = false;
+        self.chaos = false;
+        self.shake_time = 0.0;
+        self.player.colour = base_colour!();
+        self.balls[0].set_colour(base_colour!());
+    }
+
+    // Move the ball through this step, bouncing off whatever is in the way.
+    // Every time it hits something it moves up to the point of contact, the
+    // hit is dealt with, and it carries on for the rest of the step.
+    //
+    // Everything the ball touches at the same moment is dealt with together,
+    // so it only bounces once, however many bricks it touched.
+    fn move_ball(&mut self, ball: usize, delta_time: f32) {
+        let mut remaining = delta_time;
+        for _ in 0..MAX_BOUNCES_PER_STEP {
+            if self.balls[ball].is_stuck() || remaining <= 0.0 {
+                break;
+            }
+            let motion = self.balls[ball].object().velocity * remaining;
+            let (time, contacts) = match self.first_contacts(ball, motion) {
+                Some(first) => first,
+                None => {
+                    self.balls[ball].translate(motion);
+                    break;
+                },
+            };
+            self.balls[ball].translate(motion * time);
+            remaining *= 1.0 - time;
+
+            // Work out the normals before hitting anything, while all the
+            // bricks that were touched are still there to flatten corners.
+            let contacts: Vec<_> = contacts.into_iter()
+                .map(|(contact, obstacle)| match obstacle {
+                    Obstacle::Brick(_) =>
+                        (self.surface_normal(ball, contact.normal), obstacle),
+                    _ => (contact.normal, obstacle),
+                })
+                .collect();
+            let mut normals = Vec::with_capacity(contacts.len());
+            let mut paddle = false;
+            for (normal, obstacle) in contacts {
+                match obstacle {
+                    Obstacle::Wall => normals.push(normal),
+                    Obstacle::Brick(index) => {
+                        if self.hit_brick(ball, index) {
+                            normals.push(normal);
+                        }
+                    },
+                    Obstacle::Paddle => paddle = true,
+                }
+            }
+            // The paddle decides where the ball goes by itself.
+            if paddle {
+                self.hit_paddle(ball);
+            }
+            else {
+                self.balls[ball].bounce(&normals);
+            }
+        }
+    }
+
+    // Everything the ball would touch first if it moved by motion, and how
+    // far along motion that happens. Usually it's just one thing, but the
+    // ball can touch several at once, e.g. two bricks either side of a seam.
+    fn first_contacts(&mut self, ball: usize, motion: cgmath::Vector2<f32>)
+        -> Option<(f32, Vec<(collision::Contact, Obstacle)>)>
+    {
+        use cgmath::InnerSpace;
+
+        // Contacts this close together (in pixels along the path) happen
+        // at the same time.
+        const SAME_TIME: f32 = 0.01;
+
+        // A ball that isn't going anywhere can't hit anything.
+        if motion.x == 0.0 && motion.y == 0.0 {
+            return None;
+        }
+        let ball = &self.balls[ball];
+        let center = ball.center();
+        let radius = ball.radius();
+        let mut contacts: Vec<_> =
+            ball.wall_contacts(motion, self.width as f32).into_iter()
+            .map(|contact| (contact, Obstacle::Wall))
+            .collect();
+        // Only the bricks near the ball's path need checking.
+        let level = &self.levels[self.level - 1];
+        let end = center + motion;
+        let reach = cgmath::vec2(radius, radius);
+        level.bricks_near(
+            cgmath::vec2(center.x.min(end.x), center.y.min(end.y)) - reach,
+            cgmath::vec2(center.x.max(end.x), center.y.max(end.y)) + reach,
+            &mut self.nearby_bricks);
+        for &index in &self.nearby_bricks {
+            let brick = level.brick(index);
+            if !brick.is_destroyed() {
+                let obj = brick.object();
+                let contact = collision::sweep_circle_aabb(
+                    center, radius, motion, obj.position, obj.size);
+                if let Some(contact) = contact {
+                    contacts.push((contact, Obstacle::Brick(index)));
+                }
+            }
+        }
+        let contact = collision::sweep_circle_aabb(
+            center, radius, motion, self.player.position, self.player.size);
+        if let Some(contact) = contact {
+            contacts.push((contact, Obstacle::Paddle));
+        }
+
+        if contacts.is_empty() {
+            return None;
+        }
+        let first = contacts.iter()
+            .map(|&(contact, _)| contact.time)
+            .fold(::std::f32::INFINITY, f32::min);
+        let tolerance = SAME_TIME / motion.magnitude();
+        contacts.retain(|&(contact, _)| contact.time <= first + tolerance);
+        Some((first, contacts))
+    }
+
+    // The normal to bounce off, given the normal where the ball touched
+    // something. See collision::flatten_corner.
+    fn surface_normal(&mut self, ball: usize, normal: cgmath::Vector2<f32>)
+        -> cgmath::Vector2<f32>
+    {
+        let level = &self.levels[self.level - 1];
+        let ball = &self.balls[ball];
+        let nearby = &mut self.nearby_bricks;
+        collision::flatten_corner(
+            ball.center(), ball.radius(), normal,
+            |point| level.is_brick_at(point, nearby))
+    }
+
+    // Returns whether the ball should bounce off the brick.
+    fn hit_brick(&mut self, ball: usize, index: usize) -> bool {
+        // Pass-through lets the ball carry on through non-solid bricks,
+        // however many hits they have left.
+        let pass_through = self.balls[ball].is_pass_through();
+        let (hit, points, position) = {
+            let brick = self.levels[self.level - 1].brick_mut(index);
+            let hit = if pass_through { brick.smash() } else { brick.hit() };
+            if hit == Hit::Destroyed {
+                brick.play(self.brick_break.clone());
+            }
+            (hit, brick.points(), brick.object().position)
+        };
+        match hit {
+            Hit::Destroyed => {
+                self.score.brick_hit(points);
+                self.spawn_powerups(position);
+                self.audio.play_sound("brick");
+            },
+            Hit::Damaged => {
+                self.score.brick_hit(0);
+                self.audio.play_sound("brick");
+            },
+            Hit::Solid => {
+                self.shake_time = 0.05;
+                self.audio.play_sound("solid");
+            },
+        }
+        hit == Hit::Solid || !pass_through
+    }
+
+    fn hit_paddle(&mut self, ball: usize) {
+        self.balls[ball].rebound_paddle(&self.player, &self.config);
+        self.score.end_combo();
+        self.audio.play_sound("paddle");
     }
 
     fn do_collisions(&mut self) {
-        for brick in self.levels[self.level - 1].bricks_iter_mut() {
-            if !brick.is_destroyed {
-                if let Collision::Yes(direction, penetration) =
-                       self.ball.check_collision(brick)
-               {
-                    if !brick.is_solid {
-                        brick.is_destroyed = true;
-                    }
-                    self.ball.rebound_brick(direction, penetration);
+        // The ball can't move into the paddle without move_ball noticing, but
+        // the paddle can still move into the ball.
+        for i in 0..self.balls.len() {
+            let ball = &self.balls[i];
+            if !ball.is_stuck() && ball.object().velocity.y > 0.0 {
+                let paddle = self.player.shape();
+                if collision::overlap(&ball.shape(), &paddle).is_some() {
+                    self.hit_paddle(i);
                 }
             }
         }
 
-        if !self.ball.is_stuck() {
-            if let Collision::Yes(_, _) =
-                   self.ball.check_collision(&self.player)
-            {
-                self.ball.rebound_paddle(&self.player);
+        // Balls stuck to the paddle are out of play until they're released.
+        for i in 0..self.balls.len() {
+            let (first, rest) = self.balls.split_at_mut(i + 1);
+            let ball = &mut first[i];
+            if ball.is_stuck() {
+                continue;
+            }
+            for other in rest.iter_mut().filter(|b| !b.is_stuck()) {
+                ball.collide(other);
+            }
+        }
+
+        for i in 0..self.powerups.len() {
+            if !self.powerups[i].is_destroyed() {
+                let collected = collision::overlap(
+                    &self.powerups[i].object().shape(),
+                    &self.player.shape()).is_some();
+                if collected {
+                    let kind = self.powerups[i].kind();
+                    self.activate_powerup(kind);
+                    self.powerups[i].activate();
+                    self.audio.play_sound("powerup");
+                }
+            }
+        }
+    }
+
+    // Powerups animate themselves as they move.
+    fn update_animations(&mut self, delta_time: f32) {
+        for ball in self.balls.iter_mut() {
+            ball.animate(delta_time);
+        }
+        for brick in self.levels[self.level - 1].bricks_iter_mut() {
+            brick.animate(delta_time);
+        }
+    }
+
+    fn spawn_powerups(&mut self, position: cgmath::Vector2<f32>) {
+        for kind in PowerUpKind::all() {
+            if self.rng.gen_range(0, kind.spawn_chance()) == 0 {
+                self.powerups.push(PowerUp::new(*kind, position));
+            }
+        }
+    }
+
+    fn update_powerups(&mut self, delta_time: f32) {
+        let height = self.height as f32;
+        let mut expired = Vec::new();
+        for powerup in self.powerups.iter_mut() {
+            if powerup.update(delta_time, height) {
+                expired.push(powerup.kind());
+            }
+        }
+        self.powerups.retain(|p| !p.is_spent());
+
+        // Only undo the effect if there isn't another powerup of the same
+        // kind that is still going.
+        for kind in expired {
+            let still_active = self.powerups.iter()
+                .any(|p| p.kind() == kind && p.is_activated());
+            if !still_active {
+                self.deactivate_powerup(kind);
             }
         }
     }
 
-    pub fn render<C: gfx::CommandBuffer<R>>(
-        &mut self, encoder: &mut gfx::Encoder<R, C>)
+    fn activate_powerup(&mut self, kind: PowerUpKind) {
+        use self::cgmath::vec3;
+
+        match kind {
+            PowerUpKind::Speed => {
+                for ball in self.balls.iter_mut() {
+                    ball.scale_velocity(1.2);
+                }
+            },
+            PowerUpKind::Sticky => {
+                for ball in self.balls.iter_mut() {
+                    ball.set_sticky(true);
+                }
+                self.player.colour = vec3(1.0, 0.5, 1.0);
+            },
+            PowerUpKind::PassThrough => {
+                for ball in self.balls.iter_mut() {
+                    ball.set_pass_through(true);
+                    ball.set_colour(vec3(1.0, 0.5, 0.5));
+                }
+            },
+            PowerUpKind::PadSizeIncrease => {
+                self.player.size.x += 50.0;
+            },
+            // Confuse and chaos don't mix well, so only one can be active.
+            PowerUpKind::Confuse => {
+                if !self.chaos {
+                    self.confuse = true;
+                }
+            },
+            PowerUpKind::Chaos => {
+                if !self.confuse {
+                    self.chaos = true;
+                }
+            },
+            PowerUpKind::Multiball => self.split_ball(),
+        }
+    }
+
+    // Speed and paddle size increases and extra balls are permanent, so there
+    // is nothing to undo for them.
+    fn deactivate_powerup(&mut self, kind: PowerUpKind) {
+        match kind {
+            PowerUpKind::Speed | PowerUpKind::PadSizeIncrease
+                | PowerUpKind::Multiball => {},
+            PowerUpKind::Sticky => {
+                for ball in self.balls.iter_mut() {
+                    ball.set_sticky(false);
+                }
+                self.player.colour = base_colour!();
+            },
+            PowerUpKind::PassThrough => {
+                for ball in self.balls.iter_mut() {
+                    ball.set_pass_through(false);
+                    ball.set_colour(base_colour!());
+                }
+            },
+            PowerUpKind::Confuse => {
+                self.confuse = false;
+            },
+            PowerUpKind::Chaos => {
+                self.chaos = false;
+            },
+        }
+    }
+
+    // Two more balls head off either side of one that's already in play (or
+    // stuck to the paddle, if none are). They pick up whatever powerups the
+    // ball already had.
+    fn split_ball(&mut self) {
+        // Radians either side of the original ball.
+        const SPREAD: f32 = 0.4;
+
+        if self.balls.len() >= MAX_BALLS {
+            return;
+        }
+        let ball = self.balls.iter()
+            .find(|b| !b.is_stuck())
+            .unwrap_or(&self.balls[0])
+            .clone();
+        for angle in &[-SPREAD, SPREAD] {
+            if self.balls.len() < MAX_BALLS {
+                self.balls.push(ball.split(*angle));
+            }
+        }
+    }
+}
+
+
+// The game doesn't need a window or any graphics, so whole games can be
+// played out here. Levels are made from strings rather than loaded from the
+// assets, so the tests can say exactly what's in them.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::vec2;
+
+    use audio::{RecordingAudio, SoundLog};
+
+    const WIDTH: i32 = 800;
+    const HEIGHT: i32 = 600;
+    const STEP: f32 = 1.0 / 120.0;
+    // Nothing in these tests should take anywhere near this long.
+    const MAX_STEPS: usize = 120 * 60;
+
+    fn game(levels: &[&str]) -> Game {
+        let levels = levels.iter()
+            .map(|data| GameLevel::parse(
+                data, WIDTH as u32, (HEIGHT / 2) as u32).unwrap())
+            .collect();
+        Game::with_levels(WIDTH, HEIGHT, levels, GameConfig::default())
+            .unwrap()
+    }
+
+    fn input(actions: &[Action]) -> InputState {
+        let mut input = InputState::new();
+        for &action in actions {
+            input.press(action);
+        }
+        input
+    }
+
+    // Go from the menu into the first level, with the ball on the paddle.
+    fn start(game: &mut Game) {
+        game.step(STEP, &input(&[Action::Confirm]));
+        game.step(STEP, &input(&[]));
+    }
+
+    // Keep stepping until done says so. Returns false if it never did.
+    fn step_until<F>(game: &mut Game, input: &InputState, done: F) -> bool
+        where F: Fn(&Game) -> bool
     {
-        if let GameState::Active = self.state
-        {
-            self.sprite_renderer.draw_sprite(
-                self.resources.texture("background").unwrap(),
-                cgmath::vec2(0.0, 0.0),
-                cgmath::vec2(self.width as f32, self.height as f32),
-                0.0,
-                base_colour!(),
-                encoder);
+        for _ in 0..MAX_STEPS {
+            if done(game) {
+                return true;
+            }
+            game.step(STEP, input);
+        }
+        done(game)
+    }
+
+    // Send the ball straight down the left hand side, well away from the
+    // paddle, and wait for it to fall off the bottom.
+    fn lose_ball(game: &mut Game) {
+        let lives = game.lives();
+        let state = *game.state();
+        let x = 0.0;
+        let y = game.height as f32 - 100.0;
+        game.balls[0].reset(vec2(x, y), vec2(0.0, 500.0));
+        game.balls[0].release();
+        assert!(step_until(game, &input(&[]),
+                           |g| g.lives() != lives || *g.state() != state));
+    }
+
+    // Play the game's sounds into a log instead of the speakers.
+    fn record_sounds(game: &mut Game) -> SoundLog {
+        let audio = RecordingAudio::new();
+        let log = audio.log();
+        game.set_audio(AudioManager::new(audio));
+        log
+    }
+
+    fn played(log: &SoundLog, sound: &str) -> bool {
+        log.borrow().iter().any(|s| s == sound)
+    }
+
+    fn destroyed_bricks(game: &Game) -> usize {
+        game.current_level().bricks_iter()
+            .filter(|b| b.is_destroyed())
+            .count()
+    }
+
+    #[test]
+    fn starts_in_the_menu() {
+        let mut game = game(&["2 2"]);
+        assert_eq!(*game.state(), GameState::Menu);
+        // Nothing moves until the game has started.
+        game.step(STEP, &input(&[Action::Launch]));
+        assert!(game.balls()[0].is_stuck());
+
+        start(&mut game);
+        assert_eq!(*game.state(), GameState::Active);
+        assert_eq!(game.lives(), DEFAULT_LIVES);
+        assert_eq!(game.balls().len(), 1);
+        assert!(game.balls()[0].is_stuck());
+    }
+
+    #[test]
+    fn ball_destroys_bricks() {
+        let mut game = game(&["2 2 2 2\n0 0 0 0\n0 0 0 0"]);
+        start(&mut game);
+        assert!(step_until(&mut game, &input(&[Action::Launch]),
+                           |g| destroyed_bricks(g) > 0));
+        assert!(game.score().points() > 0);
+        assert_eq!(*game.state(), GameState::Active);
+    }
+
+    #[test]
+    fn solid_bricks_are_never_destroyed() {
+        // A level with nothing but solid bricks is already cleared, so it
+        // needs something else in it too.
+        let mut game = game(&["1 1 1 1\n0 0 0 0\n0 0 0 2"]);
+        start(&mut game);
+        let launch = input(&[Action::Launch]);
+        for _ in 0..120 * 5 {
+            game.step(STEP, &launch);
+        }
+        assert!(game.current_level().bricks_iter()
+                .filter(|b| b.is_solid())
+                .all(|b| !b.is_destroyed()));
+    }
+
+    // Send the ball off from center without it touching the paddle.
+    fn launch_from(game: &mut Game, center: cgmath::Vector2<f32>,
+                   velocity: cgmath::Vector2<f32>) {
+        let radius = game.balls[0].radius();
+        game.balls[0].reset(center - vec2(radius, radius), velocity);
+        game.balls[0].release();
+    }
+
+    #[test]
+    fn seam_between_bricks_bounces_once() {
+        // The bottom row has two bricks meeting at x = 400, and the ball
+        // goes straight up the join. It hits both at once, but should only
+        // bounce once, or it'd carry on up through them.
+        let mut game = game(&["2 0 0 2\n0 0 0 0\n0 2 2 0"]);
+        start(&mut game);
+        launch_from(&mut game, vec2(400.0, 400.0), vec2(0.0, -350.0));
+        assert!(step_until(&mut game, &input(&[]),
+                           |g| destroyed_bricks(g) > 0));
+        assert_eq!(destroyed_bricks(&game), 2);
+        assert_eq!(game.balls[0].object().velocity, vec2(0.0, 350.0));
+    }
+
+    #[test]
+    fn inside_corner_reverses_both() {
+        // A solid brick in the top left makes an inside corner with the
+        // left wall. Going straight into it, the ball touches both at the
+        // same moment and should come straight back out.
+        let mut game = game(&["1 0 0 0\n0 0 0 0\n0 0 0 2"]);
+        start(&mut game);
+        let radius = game.balls[0].radius();
+        let corner = vec2(radius, 100.0 + radius);
+        launch_from(&mut game, corner + vec2(50.0, 50.0),
+                    vec2(-300.0, -300.0));
+        assert!(step_until(&mut game, &input(&[]),
+                           |g| g.balls[0].object().velocity.y > 0.0));
+        assert_eq!(game.balls[0].object().velocity, vec2(300.0, 300.0));
+    }
+
+    #[test]
+    fn ball_that_isnt_moving() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        launch_from(&mut game, vec2(400.0, 400.0), vec2(0.0, 0.0));
+        game.step(STEP, &input(&[]));
+        assert_eq!(game.balls[0].center(), vec2(400.0, 400.0));
+    }
+
+    #[test]
+    fn new_config_changes_the_launch_velocity() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        let mut config = GameConfig::default();
+        config.ball.velocity = [0.0, -500.0];
+        game.set_config(config);
+        game.step(STEP, &input(&[Action::Launch]));
+        assert!(!game.balls[0].is_stuck());
+        assert_eq!(game.balls[0].object().velocity, vec2(0.0, -500.0));
+    }
+
+    #[test]
+    fn balls_pushed_into_a_wall_come_back_out() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        let radius = game.balls[0].radius();
+        // One ball creeping towards the left wall, and another catching it
+        // up. When they bounce, the first one is pushed into the wall.
+        launch_from(&mut game, vec2(radius + 1.0, 400.0), vec2(-10.0, 0.0));
+        let mut other = game.balls[0].clone();
+        other.translate(vec2(radius * 2.0 - 4.0, 0.0));
+        other.set_velocity(vec2(-300.0, 0.0));
+        game.balls.push(other);
+
+        game.step(STEP, &input(&[]));
+        assert!(game.balls[0].object().position.x < 0.0);
+        assert_eq!(game.balls[0].object().velocity, vec2(-300.0, 0.0));
+
+        // It bounces off the wall, and then gets knocked back into it by
+        // the other ball again, but ends up heading back out.
+        game.step(STEP, &input(&[]));
+        game.step(STEP, &input(&[]));
+        assert!(game.balls[0].object().velocity.x > 0.0);
+        assert!(game.balls[1].object().velocity.x > 0.0);
+        assert!(step_until(&mut game, &input(&[]),
+                           |g| g.balls[0].object().position.x >= 0.0));
+    }
+
+    #[test]
+    fn a_life_is_only_lost_with_the_last_ball() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        launch_from(&mut game, vec2(100.0, 400.0), vec2(0.0, -100.0));
+        // Another ball heading off the bottom, well away from the paddle.
+        let mut other = game.balls[0].clone();
+        other.reset(vec2(0.0, HEIGHT as f32 - 100.0), vec2(0.0, 500.0));
+        other.release();
+        game.balls.push(other);
+        assert!(step_until(&mut game, &input(&[]),
+                           |g| g.balls().len() == 1));
+        assert_eq!(game.lives(), DEFAULT_LIVES);
+        assert_eq!(*game.state(), GameState::Active);
+        assert!(!game.balls[0].is_stuck());
 
-            self.levels[self.level - 1].draw(
-                &mut self.sprite_renderer, encoder);
+        // Now the last one goes.
+        lose_ball(&mut game);
+        assert_eq!(game.lives(), DEFAULT_LIVES - 1);
+        assert_eq!(game.balls().len(), 1);
+        assert!(game.balls[0].is_stuck());
+    }
+
+    #[test]
+    fn touching_the_paddle_ends_the_combo() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        game.score.brick_hit(10);
+        game.score.brick_hit(10);
+        // Drop the ball straight onto the middle of the paddle.
+        let paddle = game.player().position;
+        let x = paddle.x + game.player().size.x / 2.0;
+        launch_from(&mut game, vec2(x, paddle.y - 100.0), vec2(0.0, 300.0));
+        assert!(step_until(&mut game, &input(&[]),
+                           |g| g.balls[0].object().velocity.y < 0.0));
+        assert_eq!(game.score().combo(), 0);
+        assert_eq!(game.score().best_combo(), 2);
+    }
+
+    #[test]
+    fn losing_the_ball_ends_the_combo() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        game.score.brick_hit(10);
+        lose_ball(&mut game);
+        assert_eq!(game.score().combo(), 0);
+    }
+
+    #[test]
+    fn losing_the_ball_costs_a_life() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        lose_ball(&mut game);
+        assert_eq!(game.lives(), DEFAULT_LIVES - 1);
+        assert_eq!(*game.state(), GameState::Active);
+        // The ball goes back on the paddle.
+        assert!(game.balls()[0].is_stuck());
+        assert!(!game.balls()[0].is_below(HEIGHT as f32));
+    }
+
+    #[test]
+    fn losing_every_life_ends_the_game() {
+        let mut game = game(&["2 2"]);
+        start(&mut game);
+        for _ in 0..DEFAULT_LIVES {
+            lose_ball(&mut game);
+        }
+        assert_eq!(*game.state(), GameState::Lose);
+        assert_eq!(game.lives(), 0);
+
+        game.step(STEP, &input(&[Action::Confirm]));
+        assert_eq!(*game.state(), GameState::Menu);
+        // A new game starts with all its lives back.
+        game.step(STEP, &input(&[]));
+        start(&mut game);
+        assert_eq!(game.lives(), DEFAULT_LIVES);
+    }
+
+    #[test]
+    fn clearing_a_level_moves_on_to_the_next() {
+        // One brick each, as wide as the screen, so the ball can't miss.
+        let mut game = game(&["2", "3"]);
+        start(&mut game);
+        assert!(step_until(&mut game, &input(&[Action::Launch]),
+                           |g| g.level() == 2));
+        assert_eq!(*game.state(), GameState::Active);
+        let events: Vec<_> = game.drain_events().collect();
+        assert_eq!(events, vec![GameEvent::LevelCleared(1)]);
+        // The next level starts with the ball back on the paddle.
+        assert!(game.balls()[0].is_stuck());
+    }
+
+    #[test]
+    fn clearing_the_last_level_wins() {
+        let mut game = game(&["2"]);
+        start(&mut game);
+        assert!(step_until(&mut game, &input(&[Action::Launch]),
+                           |g| *g.state() != GameState::Active));
+        assert_eq!(*game.state(), GameState::Win);
+        let events: Vec<_> = game.drain_events().collect();
+        assert!(events.contains(&GameEvent::GameWon));
+
+        game.step(STEP, &input(&[Action::Confirm]));
+        assert_eq!(*game.state(), GameState::Menu);
+        // The level is ready to be played again.
+        assert_eq!(destroyed_bricks(&game), 0);
+    }
+
+    #[test]
+    fn breaking_a_brick_plays_a_sound() {
+        let mut game = game(&["2 2 2 2\n0 0 0 0\n0 0 0 0"]);
+        let log = record_sounds(&mut game);
+        start(&mut game);
+        assert!(step_until(&mut game, &input(&[Action::Launch]),
+                           |g| destroyed_bricks(g) > 0));
+        assert!(played(&log, "brick"));
+        assert!(!played(&log, "solid"));
+    }
+
+    #[test]
+    fn hitting_a_solid_brick_plays_a_sound() {
+        let mut game = game(&["1 1 1 1\n0 0 0 0\n0 0 0 2"]);
+        let log = record_sounds(&mut game);
+        start(&mut game);
+        assert!(step_until(&mut game, &input(&[Action::Launch]),
+                           |_| played(&log, "solid")));
+        // The screen shakes too.
+        assert!(game.screen_effects().shake);
+    }
+
+    #[test]
+    fn hitting_the_paddle_plays_a_sound() {
+        let mut game = game(&["2 2"]);
+        let log = record_sounds(&mut game);
+        start(&mut game);
+        // Drop the ball straight onto the middle of the paddle.
+        let paddle = game.player().position + game.player().size / 2.0;
+        let radius = game.balls()[0].radius();
+        game.balls[0].reset(
+            vec2(paddle.x - radius, paddle.y - 150.0), vec2(0.0, 400.0));
+        game.balls[0].release();
+        assert!(step_until(&mut game, &input(&[]),
+                           |_| played(&log, "paddle")));
+        assert!(game.balls()[0].object().velocity.y < 0.0);
+        assert_eq!(game.lives(), DEFAULT_LIVES);
+    }
+
+    #[test]
+    fn collecting_a_powerup_plays_a_sound() {
+        let mut game = game(&["2 2"]);
+        let log = record_sounds(&mut game);
+        start(&mut game);
+        // Drop one straight onto the paddle.
+        let position = game.player().position - vec2(0.0, 50.0);
+        game.powerups.push(PowerUp::new(PowerUpKind::Sticky, position));
+        assert!(step_until(&mut game, &input(&[]),
+                           |_| played(&log, "powerup")));
+        assert!(game.balls()[0].is_sticky());
+        // Only once, however long it takes to fall past the paddle.
+        for _ in 0..60 {
+            game.step(STEP, &input(&[]));
+        }
+        let count = log.borrow().iter().filter(|s| *s == "powerup").count();
+        assert_eq!(count, 1);
+    }
+
+    // Everything about the game that could differ between two runs, as raw
+    // bits so that even the smallest difference in a float shows up.
+    fn snapshot(game: &Game) -> Vec<u32> {
+        let mut bits = vec![
+            *game.state() as u32, game.level() as u32, game.lives(),
+            game.score().points(), game.score().combo(), game.time.to_bits(),
+            game.player().position.x.to_bits(), game.player().size.x.to_bits(),
+        ];
+        for ball in game.balls() {
+            let obj = ball.object();
+            bits.extend(&[
+                obj.position.x.to_bits(), obj.position.y.to_bits(),
+                obj.velocity.x.to_bits(), obj.velocity.y.to_bits(),
+                ball.is_stuck() as u32,
+            ]);
+        }
+        for brick in game.current_level().bricks_iter() {
+            bits.push(brick.hit_points());
+        }
+        for powerup in game.powerups_iter() {
+            let obj = powerup.object();
+            bits.extend(&[
+                powerup.kind() as u32,
+                obj.position.x.to_bits(), obj.position.y.to_bits(),
+            ]);
+        }
+        bits
+    }
+
+    // The same made up player for every run: they wander left and right,
+    // and launch the ball whenever it's on the paddle.
+    fn scripted_input(step: usize) -> InputState {
+        let mut actions = vec![Action::Launch];
+        match (step / 90) % 3 {
+            0 => actions.push(Action::MoveLeft),
+            1 => actions.push(Action::MoveRight),
+            _ => {},
+        }
+        if step == 0 {
+            actions.push(Action::Confirm);
+        }
+        input(&actions)
+    }
 
-            self.player.draw(&mut self.sprite_renderer, encoder);
-            self.ball.draw(&mut self.sprite_renderer, encoder);
+    #[test]
+    fn same_input_gives_the_same_game() {
+        let level = "5 5 5 5 5 5 5 5\n\
+                     4 4 4 1 1 4 4 4\n\
+                     3 3 3 3 3 3 3 3\n\
+                     2 2 0 0 0 0 2 2";
+        let mut first = game(&[level]);
+        let mut second = game(&[level]);
+        for step in 0..120 * 30 {
+            let input = scripted_input(step);
+            first.step(STEP, &input);
+            second.step(STEP, &input);
+            assert_eq!(snapshot(&first), snapshot(&second),
+                       "the games differ after step {}", step);
         }
+        // Make sure something actually happened.
+        assert!(first.score().points() > 0);
     }
 }
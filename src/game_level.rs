@@ -12,19 +12,64 @@
 //
 // The original code was modified by Adrian Chan in order to port it to Rust.
 
+// Level files come in two formats.
+//
+// The original format (version 1) is nothing but a grid of tile ids, one row
+// per line, separated by whitespace:
+//
+//     5 5 5 5
+//     1 0 0 1
+//
+// Version 2 adds a TOML header in front of the grid, separated from it by a
+// line containing only "---". Everything in the header except the version is
+// optional:
+//
+//     version = 2
+//     name = "Checkerboard"
+//     # The name of a texture loaded by the renderer.
+//     background = "background"
+//     # Multiplies the ball's starting velocity. Must be more than 0.
+//     ball_speed = 1.2
+//
+//     # Change the colour of the built-in tiles.
+//     [palette]
+//     2 = [0.9, 0.1, 0.1]
+//
+//     # Define new tiles, which can be used in the grid by name. They start
+//     # out as a copy of the "base" tile (white if there isn't one).
+//     [tiles.T]
+//     base = 4
+//     hits = 3
+//     # Points for destroying it. Defaults to the base tile's points for
+//     # every hit it takes.
+//     points = 100
+//     ---
+//     5 T 5 T
+//     1 0 0 1
+//
+// Any file without a "---" line is read as version 1, so the original levels
+// still load without any changes.
+
+use std::collections::HashMap;
 use std::fs;
 use std::io;
+use std::io::Read;
 use std::path::Path;
 
 use cgmath;
-use gfx;
+use toml;
 
+use brick::Brick;
 use errors::*;
 use game_object::GameObject;
-use renderer;
-use resource_manager::ResourceManager;
+use spatial_grid::SpatialGrid;
+
 
+const FORMAT_VERSION: u32 = 2;
+const HEADER_END: &str = "---";
 
+
+#[derive(Clone, Copy)]
 enum TileKind {
     Empty,
     Solid,
@@ -35,25 +80,20 @@ enum TileKind {
     White,
 }
 
-impl ::std::convert::From<u8> for TileKind {
-    fn from(n: u8) -> TileKind {
+impl TileKind {
+    // Only ids 0 to 5 mean anything. White can't be used directly -- it's
+    // what custom tiles start from if they don't name a base tile.
+    fn from_id(n: u8) -> Option<TileKind> {
         match n {
-            0 => TileKind::Empty,
-            1 => TileKind::Solid,
-            2 => TileKind::Blue,
-            3 => TileKind::Green,
-            4 => TileKind::Tan,
-            5 => TileKind::Orange,
-            _ => TileKind::White,
+            0 => Some(TileKind::Empty),
+            1 => Some(TileKind::Solid),
+            2 => Some(TileKind::Blue),
+            3 => Some(TileKind::Green),
+            4 => Some(TileKind::Tan),
+            5 => Some(TileKind::Orange),
+            _ => None,
         }
     }
-}
-
-impl TileKind {
-    fn try_from(s: &str) -> Result<TileKind> {
-        let n: u8 = s.parse()?;
-        Ok(TileKind::from(n))
-    }
 
     fn colour(&self) -> cgmath::Vector3<f32> {
         use self::TileKind::*;
@@ -77,109 +117,741 @@ impl TileKind {
         }
     }
 
-    fn texture_name(&self) -> &str {
+    // What a brick is worth when it's destroyed. Solid bricks can't be.
+    fn points(&self) -> u32 {
+        use self::TileKind::*;
+
+        match *self {
+            Empty | Solid => 0,
+            Blue | White => 10,
+            Green => 20,
+            Tan => 30,
+            Orange => 50,
+        }
+    }
+
+    // Empty tiles don't make a brick.
+    fn tile(&self) -> Option<Tile> {
         match *self {
-            TileKind::Empty => panic!("empty tiles have no texture"),
-            TileKind::Solid => "block_solid",
-            _ => "block",
+            TileKind::Empty => None,
+            _ => Some(Tile {
+                colour: self.colour(),
+                solid: self.is_solid(),
+                hits: 1,
+                points: self.points(),
+            }),
+        }
+    }
+}
+
+// Everything we need to know to make a brick.
+#[derive(Clone)]
+struct Tile {
+    colour: cgmath::Vector3<f32>,
+    solid: bool,
+    // How many hits it takes to destroy the brick. Solid bricks ignore this.
+    hits: u32,
+    points: u32,
+}
+
+impl Tile {
+    fn texture_name(&self) -> &str {
+        if self.solid { "block_solid" } else { "block" }
+    }
+}
+
+// Turns the tokens in the grid into tiles, taking into account any changes
+// made by the level's header.
+#[derive(Default)]
+struct TileSet {
+    palette: HashMap<u8, cgmath::Vector3<f32>>,
+    custom: HashMap<String, Tile>,
+}
+
+impl TileSet {
+    fn from_header(header: &Header, file: &str) -> Result<Self> {
+        let mut tile_set = Self::default();
+
+        for (id, colour) in &header.palette {
+            let n = match builtin_id(id) {
+                Some(n) => n,
+                None => return Err(header_error(
+                    file, format!("{} in [palette] is not a tile id", id))),
+            };
+            tile_set.palette.insert(n, (*colour).into());
+        }
+
+        for (symbol, def) in &header.tiles {
+            if symbol.parse::<u8>().is_ok() {
+                return Err(header_error(
+                    file,
+                    format!("tile {} clashes with the built-in tile ids",
+                            symbol)));
+            }
+            if symbol.is_empty() || symbol.chars().any(char::is_whitespace) {
+                return Err(header_error(
+                    file,
+                    format!("tile name {:?} can't be used in the grid",
+                            symbol)));
+            }
+            let base = match def.base {
+                Some(id) => match TileKind::from_id(id) {
+                    Some(TileKind::Empty) => return Err(header_error(
+                        file,
+                        format!("tile {} can't be based on an empty tile",
+                                symbol))),
+                    Some(_) => tile_set.builtin(id),
+                    None => return Err(header_error(
+                        file,
+                        format!("tile {} has an unknown base {}",
+                                symbol, id))),
+                },
+                None => TileKind::White.tile(),
+            };
+            let mut tile = base.expect("base tiles are never empty");
+            if let Some(colour) = def.colour {
+                tile.colour = colour.into();
+            }
+            if let Some(solid) = def.solid {
+                tile.solid = solid;
+            }
+            if let Some(hits) = def.hits {
+                if hits == 0 {
+                    return Err(header_error(
+                        file, format!("tile {} needs at least one hit", symbol)));
+                }
+                tile.hits = hits;
+            }
+            tile.points = match def.points {
+                Some(points) => points,
+                None => match tile.points.checked_mul(tile.hits) {
+                    Some(points) => points,
+                    None => return Err(header_error(
+                        file,
+                        format!("tile {} has too many hits to score", symbol))),
+                },
+            };
+            tile_set.custom.insert(symbol.clone(), tile);
+        }
+
+        Ok(tile_set)
+    }
+
+    fn builtin(&self, id: u8) -> Option<Tile> {
+        let mut tile = TileKind::from_id(id)?.tile()?;
+        if let Some(colour) = self.palette.get(&id) {
+            tile.colour = *colour;
+        }
+        Some(tile)
+    }
+
+    // None if there's no such tile. Some(None) if it's an empty space.
+    fn lookup(&self, token: &str) -> Option<Option<Tile>> {
+        if let Some(tile) = self.custom.get(token) {
+            return Some(Some(tile.clone()));
+        }
+        let n = builtin_id(token)?;
+        Some(self.builtin(n))
+    }
+}
+
+fn builtin_id(s: &str) -> Option<u8> {
+    s.parse().ok().and_then(|n| TileKind::from_id(n).map(|_| n))
+}
+
+fn header_error(file: &str, message: String) -> Error {
+    ErrorKind::LevelHeader(file.into(), message).into()
+}
+
+// TOML counts lines and columns from 0, and the header starts on the first
+// line of the file. The TOML message ends with the line number too, which
+// would just repeat what's at the front of ours.
+fn header_syntax_error(file: &str, error: &toml::de::Error) -> Error {
+    let mut message = error.to_string();
+    match error.line_col() {
+        Some((line, column)) => {
+            if let Some(end) = message.rfind(" at line ") {
+                message.truncate(end);
+            }
+            ErrorKind::LevelHeaderSyntax(
+                file.into(), line + 1, column + 1, message).into()
+        },
+        None => header_error(file, message),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    version: u32,
+    name: Option<String>,
+    background: Option<String>,
+    ball_speed: Option<f32>,
+    #[serde(default)]
+    palette: HashMap<String, [f32; 3]>,
+    #[serde(default)]
+    tiles: HashMap<String, TileDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TileDef {
+    base: Option<u8>,
+    colour: Option<[f32; 3]>,
+    solid: Option<bool>,
+    hits: Option<u32>,
+    points: Option<u32>,
+}
+
+// The parts of the header that the rest of the game cares about.
+#[derive(Clone, Debug)]
+pub struct LevelInfo {
+    pub name: Option<String>,
+    pub background: String,
+    pub ball_speed: f32,
+}
+
+impl Default for LevelInfo {
+    fn default() -> Self {
+        Self {
+            name: None,
+            background: "background".into(),
+            ball_speed: 1.0,
         }
     }
 }
 
-pub struct GameLevel<R: gfx::Resources> {
-    bricks: Vec<GameObject<R>>,
-    bricks_original: Vec<GameObject<R>>,
+pub struct GameLevel {
+    info: LevelInfo,
+    bricks: Vec<Brick>,
+    // Damaged and destroyed bricks go back to how they were in here when
+    // the level is reset.
+    bricks_original: Vec<Brick>,
+    // Where the bricks are, so we don't have to look through all of them to
+    // find the ones near the ball.
+    grid: SpatialGrid,
 }
 
-impl <R: gfx::Resources> GameLevel<R> {
-    pub fn new<P: AsRef<Path>, F: gfx::traits::FactoryExt<R>>(
-        path: &P, level_width: u32, level_height: u32,
-        resources: &ResourceManager<F, R>)
+impl GameLevel {
+    pub fn new<P: AsRef<Path>>(
+        path: &P, level_width: u32, level_height: u32)
+    -> Result<Self>
+    {
+        let path = path.as_ref();
+        let mut data = String::new();
+        fs::File::open(path)
+            .and_then(|f| io::BufReader::new(f).read_to_string(&mut data))
+            .chain_err(|| format!("couldn't read level {}", path.display()))?;
+        Self::parse_named(
+            &data, &path.display().to_string(), level_width, level_height)
+    }
+
+    // Build a level from level data that is already in memory, e.g. for
+    // tests that shouldn't depend on the files in the assets directory.
+    pub fn parse(data: &str, level_width: u32, level_height: u32)
     -> Result<Self>
     {
-        let tile_data = Self::read_tile_data(path.as_ref())?;
+        Self::parse_named(data, "<level data>", level_width, level_height)
+    }
+
+    // The file name is only used to make the error messages more helpful.
+    fn parse_named(
+        data: &str, file: &str, level_width: u32, level_height: u32)
+    -> Result<Self>
+    {
+        let (info, tile_set, grid, first_line) = match split_header(data) {
+            Some((header, grid, first_line)) => {
+                let header: Header = toml::from_str(header)
+                    .map_err(|e| header_syntax_error(file, &e))?;
+                if header.version != FORMAT_VERSION {
+                    return Err(header_error(
+                        file,
+                        format!("unsupported format version {}",
+                                header.version)));
+                }
+                if let Some(speed) = header.ball_speed {
+                    // Anything else would leave the ball stuck in place, or
+                    // send it down at the paddle.
+                    if !speed.is_finite() || speed <= 0.0 {
+                        return Err(header_error(
+                            file,
+                            format!("ball_speed must be more than 0, not {}",
+                                    speed)));
+                    }
+                }
+                let tile_set = TileSet::from_header(&header, file)?;
+                let defaults = LevelInfo::default();
+                let info = LevelInfo {
+                    name: header.name,
+                    background: header.background
+                        .unwrap_or(defaults.background),
+                    ball_speed: header.ball_speed
+                        .unwrap_or(defaults.ball_speed),
+                };
+                (info, tile_set, grid, first_line)
+            },
+            None => (LevelInfo::default(), TileSet::default(), data, 1),
+        };
 
+        let tile_data =
+            Self::read_tile_data(grid, first_line, file, &tile_set)?;
+        Self::from_tile_data(info, tile_data, level_width, level_height)
+    }
+
+    fn from_tile_data(
+        info: LevelInfo, tile_data: Vec<Vec<Option<Tile>>>,
+        level_width: u32, level_height: u32)
+    -> Result<Self>
+    {
+        // read_tile_data has already made sure that there's at least one
+        // row, and that all the rows are the same width.
         let height = tile_data.len();
         let width = tile_data[0].len();
         let unit_width = level_width as f32 / width as f32;
         let unit_height = level_height as f32 / height as f32;
-        if !tile_data.iter().all(|row| row.len() == width) {
-            bail!("expected all rows to be {} wide", width);
-        }
 
         let mut bricks = Vec::with_capacity(150);
+        // One brick per cell.
+        let mut grid = SpatialGrid::new(
+            cgmath::vec2(0.0, 0.0),
+            cgmath::vec2(level_width as f32, level_height as f32),
+            cgmath::vec2(unit_width, unit_height));
         for (y, tile_row) in tile_data.iter().enumerate() {
             for (x, tile) in tile_row.iter().enumerate() {
                 use self::cgmath::vec2;
 
-                if let &TileKind::Empty = tile {
-                    continue;
-                }
+                let tile = match *tile {
+                    Some(ref tile) => tile,
+                    None => continue,
+                };
 
                 let pos = vec2(unit_width * x as f32, unit_height * y as f32);
                 let size = vec2(unit_width, unit_height);
-                let colour = tile.colour();
-                let sprite = {
-                    let texture_name = tile.texture_name();
-                    let texture = resources.texture(texture_name);
-                    if let None = texture {
-                        bail!("no texture resource for {}", texture_name);
-                    }
-                    texture.unwrap()
-                };
 
-                let mut obj = GameObject::new(pos, size, sprite, colour);
-                obj.is_solid = tile.is_solid();
-                bricks.push(obj);
+                let mut obj = GameObject::new(
+                    pos, size, tile.texture_name(), tile.colour);
+                obj.is_solid = tile.solid;
+                grid.insert(bricks.len(), pos, size);
+                bricks.push(Brick::new(obj, tile.hits, tile.points));
             }
         }
 
         Ok(Self{
+            info,
             bricks: bricks.clone(),
             bricks_original: bricks,
+            grid,
         })
     }
 
+    pub fn info(&self) -> &LevelInfo {
+        &self.info
+    }
+
     pub fn is_completed(&self) -> bool {
-        self.bricks.iter().all(|b| b.is_solid || b.is_destroyed )
+        // A damaged brick still has to be finished off.
+        self.bricks.iter().all(|b| b.is_solid() || b.is_destroyed() )
     }
 
-    pub fn draw<C: gfx::CommandBuffer<R>>(
-        &self,
-        renderer: &mut renderer::SpriteRenderer<R>,
-        encoder: &mut gfx::Encoder<R, C>)
-    {
-        for tile in self.bricks.iter().filter(|t| !t.is_destroyed) {
-            tile.draw(renderer, encoder);
-        }
+    pub fn bricks_iter(&self) -> ::std::slice::Iter<Brick> {
+        self.bricks.iter()
     }
 
-    pub fn bricks_iter_mut(&mut self) -> ::std::slice::IterMut<GameObject<R>> {
+    pub fn bricks_iter_mut(&mut self) -> ::std::slice::IterMut<Brick> {
         self.bricks.iter_mut()
     }
 
+    pub fn brick(&self, index: usize) -> &Brick {
+        &self.bricks[index]
+    }
+
+    pub fn brick_mut(&mut self, index: usize) -> &mut Brick {
+        &mut self.bricks[index]
+    }
+
+    // Fill found with the index of every brick that might overlap the area
+    // between min and max (top-left and bottom-right), including destroyed
+    // ones. Every brick that does overlap it is found, in the same order as
+    // bricks_iter.
+    pub fn bricks_near(
+        &self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>,
+        found: &mut Vec<usize>)
+    {
+        self.grid.query(min, max, found);
+    }
+
+    // Is there a brick (that hasn't been destroyed) at this point? found is
+    // only somewhere to put the nearby bricks while looking, so that the
+    // caller can keep re-using the same one, like with bricks_near.
+    pub fn is_brick_at(
+        &self, point: cgmath::Vector2<f32>, found: &mut Vec<usize>)
+        -> bool
+    {
+        self.bricks_near(point, point, found);
+        found.iter().any(|&i| {
+            let brick = &self.bricks[i];
+            let obj = brick.object();
+            !brick.is_destroyed()
+                && point.x > obj.position.x
+                && point.x < obj.position.x + obj.size.x
+                && point.y > obj.position.y
+                && point.y < obj.position.y + obj.size.y
+        })
+    }
+
     pub fn reset(&mut self) {
         self.bricks = self.bricks_original.clone();
     }
 
-    fn read_tile_data(path: &Path) -> Result<Vec<Vec<TileKind>>> {
-        use std::io::BufRead;
-        let mut tile_data = Vec::with_capacity(10);
-        let reader = io::BufReader::new(fs::File::open(path)?);
+    // Blank lines are skipped, so they can be used to space out the grid.
+    // first_line is the line number of the start of the grid within the file.
+    fn read_tile_data(
+        grid: &str, first_line: usize, file: &str, tile_set: &TileSet)
+    -> Result<Vec<Vec<Option<Tile>>>>
+    {
+        let mut tile_data: Vec<Vec<Option<Tile>>> = Vec::with_capacity(10);
 
-        for line in reader.lines() {
-            let line = line?;
-            let tiles: Result<Vec<_>> =
-                line
-                .split_whitespace()
-                .map(|s| TileKind::try_from(s) )
-                .collect();
-            tile_data.push(tiles?);
+        for (i, line) in grid.lines().enumerate() {
+            let line_number = first_line + i;
+            let mut row = Vec::with_capacity(15);
+            for (column, token) in tokens(line) {
+                match tile_set.lookup(token) {
+                    Some(tile) => row.push(tile),
+                    None => bail!(ErrorKind::LevelUnknownTile(
+                        file.into(), line_number, column, token.into())),
+                }
+            }
+            if row.is_empty() {
+                continue;
+            }
+
+            if let Some(first_row) = tile_data.first() {
+                let expected = first_row.len();
+                if row.len() != expected {
+                    // Point at the first tile too many, or just past the
+                    // end of the line if there are too few.
+                    let column = tokens(line).get(expected)
+                        .map_or(line.chars().count() + 1, |&(c, _)| c);
+                    bail!(ErrorKind::LevelRaggedRow(
+                        file.into(), line_number, column, expected, row.len()));
+                }
+            }
+            tile_data.push(row);
         }
 
-        if tile_data.len() == 0 {
-            bail!("no level data in {:?}", path);
+        if tile_data.is_empty() {
+            bail!(ErrorKind::LevelEmpty(file.into()));
         }
         Ok(tile_data)
     }
 }
+
+// The whitespace separated tokens in a line, along with the column that each
+// one starts at.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut start = None;
+    let mut found = Vec::new();
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column + 1, i)),
+            (Some((col, s)), true) => {
+                found.push((col, &line[s..i]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some((col, s)) = start {
+        found.push((col, &line[s..]));
+    }
+    found
+}
+
+// Split a version 2 level into its header, its grid, and the line number
+// that the grid starts on. Returns None for a version 1 level, which has no
+// header.
+fn split_header(data: &str) -> Option<(&str, &str, usize)> {
+    let mut offset = 0;
+    for (i, line) in data.split('\n').enumerate() {
+        let end = offset + line.len();
+        if line.trim() == HEADER_END {
+            let grid_start = (end + 1).min(data.len());
+            return Some((&data[..offset], &data[grid_start..], i + 2));
+        }
+        offset = end + 1;
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> Result<GameLevel> {
+        GameLevel::parse(data, 800, 300)
+    }
+
+    #[test]
+    fn too_many_hits_to_score() {
+        let level = "\
+version = 2
+[tiles.T]
+base = 5
+hits = 100000000
+---
+T
+";
+        match parse(level).map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelHeader(_, ref message) =>
+                assert!(message.contains("tile T"), "{}", message),
+            other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn many_hits_with_points_given() {
+        // Points for the whole brick replace the per-hit points, so there's
+        // nothing to overflow.
+        let level = "\
+version = 2
+[tiles.T]
+base = 5
+hits = 100000000
+points = 10
+---
+T
+";
+        let level = parse(level).unwrap();
+        assert_eq!(level.brick(0).points(), 10);
+    }
+
+    #[test]
+    fn ball_speed_must_be_positive() {
+        for speed in &["0.0", "-1.0", "nan", "inf"] {
+            let level = format!("version = 2\nball_speed = {}\n---\n2\n",
+                                speed);
+            match parse(&level).map(|_| ()).unwrap_err().kind() {
+                &ErrorKind::LevelHeader(_, ref message) =>
+                    assert!(message.contains("ball_speed"), "{}", message),
+                other => panic!("wrong error for {}: {}", speed, other),
+            }
+        }
+        let level = parse("version = 2\nball_speed = 0.5\n---\n2\n")
+            .unwrap();
+        assert_eq!(level.info().ball_speed, 0.5);
+    }
+
+    #[test]
+    fn unknown_tile() {
+        match parse("1 1\n9 1").map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelUnknownTile(_, line, column, ref tile) => {
+                assert_eq!((line, column), (2, 1));
+                assert_eq!(tile, "9");
+            },
+            other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_tile_after_a_header() {
+        // Lines are counted from the top of the file, not the grid.
+        let level = "version = 2\n---\n1 1\n1  X";
+        match parse(level).map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelUnknownTile(_, line, column, ref tile) => {
+                assert_eq!((line, column), (4, 4));
+                assert_eq!(tile, "X");
+            },
+            other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn ragged_rows() {
+        // Too few points just past the end of the line...
+        match parse("1 1 1\n1 1").map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelRaggedRow(_, line, column, expected, found) => {
+                assert_eq!((line, column), (2, 4));
+                assert_eq!((expected, found), (3, 2));
+            },
+            other => panic!("wrong error: {}", other),
+        }
+        // ...and too many points at the first tile that doesn't fit.
+        match parse("1 1\n1 1 1").map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelRaggedRow(_, line, column, expected, found) => {
+                assert_eq!((line, column), (2, 5));
+                assert_eq!((expected, found), (2, 3));
+            },
+            other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let level = parse("\n1 1\n\n  \n1 1\n").unwrap();
+        assert_eq!(level.bricks_iter().count(), 4);
+    }
+
+    #[test]
+    fn empty_level() {
+        for data in &["", "\n  \n", "version = 2\n---\n", "version = 2\n---"] {
+            match parse(data).map(|_| ()).unwrap_err().kind() {
+                &ErrorKind::LevelEmpty(_) => {},
+                other => panic!("wrong error for {:?}: {}", data, other),
+            }
+        }
+    }
+
+    #[test]
+    fn header_syntax_error() {
+        let level = "version = 2\nname = = \"x\"\n---\n1";
+        let error = parse(level).map(|_| ()).unwrap_err();
+        match *error.kind() {
+            ErrorKind::LevelHeaderSyntax(_, line, column, ref message) => {
+                assert_eq!((line, column), (2, 8));
+                // The position is only given once.
+                assert!(!message.contains(" at line "), "{}", message);
+            },
+            ref other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported_version() {
+        match parse("version = 3\n---\n1").map(|_| ()).unwrap_err().kind() {
+            &ErrorKind::LevelHeader(_, ref message) =>
+                assert_eq!(message, "unsupported format version 3"),
+            other => panic!("wrong error: {}", other),
+        }
+    }
+
+    #[test]
+    fn missing_version() {
+        let error = parse("name = \"x\"\n---\n1").map(|_| ()).unwrap_err();
+        let message = match *error.kind() {
+            ErrorKind::LevelHeader(_, ref message) => message,
+            ErrorKind::LevelHeaderSyntax(_, _, _, ref message) => message,
+            ref other => panic!("wrong error: {}", other),
+        };
+        assert!(message.contains("version"), "{}", message);
+    }
+
+    #[test]
+    fn version_1_is_the_same_as_version_2() {
+        // The original levels have no header, and should come out the same
+        // as if they had an empty one.
+        let grid = "1 2 3\n4 5 0\n";
+        let v1 = parse(grid).unwrap();
+        let v2 = parse(&format!("version = 2\n---\n{}", grid)).unwrap();
+        assert_eq!(v1.bricks_iter().count(), 5);
+        for (a, b) in v1.bricks_iter().zip(v2.bricks_iter()) {
+            assert_eq!(a.object().position, b.object().position);
+            assert_eq!(a.object().size, b.object().size);
+            assert_eq!(a.object().colour, b.object().colour);
+            assert_eq!(a.object().sprite, b.object().sprite);
+            assert_eq!(a.is_solid(), b.is_solid());
+            assert_eq!(a.max_hit_points(), b.max_hit_points());
+            assert_eq!(a.points(), b.points());
+        }
+        assert_eq!(v1.bricks_iter().count(), v2.bricks_iter().count());
+        assert_eq!(v1.info().name, v2.info().name);
+        assert_eq!(v1.info().background, v2.info().background);
+        assert_eq!(v1.info().ball_speed, v2.info().ball_speed);
+    }
+
+    #[test]
+    fn version_2_header_changes_tiles() {
+        let level = parse("\
+version = 2
+name = \"Test\"
+ball_speed = 1.5
+[palette]
+2 = [0.5, 0.5, 0.5]
+[tiles.T]
+base = 3
+hits = 3
+---
+2 T
+").unwrap();
+        assert_eq!(level.info().name, Some("Test".to_string()));
+        assert_eq!(level.info().ball_speed, 1.5);
+        let bricks: Vec<_> = level.bricks_iter().collect();
+        assert_eq!(bricks[0].object().colour, cgmath::vec3(0.5, 0.5, 0.5));
+        assert_eq!(bricks[1].max_hit_points(), 3);
+        // Green is worth 20, for each of the three hits.
+        assert_eq!(bricks[1].points(), 60);
+    }
+
+    #[test]
+    fn nearby_bricks_match_a_full_scan() {
+        use collision;
+        use rand;
+        use rand::{Rng, SeedableRng};
+        use self::cgmath::vec2;
+
+        const RADIUS: f32 = 12.5;
+
+        // Big enough that the bricks don't line up with anything.
+        let mut data = String::new();
+        for row in 0..25 {
+            let line: Vec<String> = (0..40)
+                .map(|column| {
+                    if row % 2 == 1 { 0 } else { 1 + (row + column) % 5 }
+                })
+                .map(|tile| tile.to_string())
+                .collect();
+            data.push_str(&line.join(" "));
+            data.push('\n');
+        }
+        let level = parse(&data).unwrap();
+
+        // Every brick the ball would hit going from center along motion.
+        let hits = |bricks: &[usize], center: cgmath::Vector2<f32>,
+                    motion: cgmath::Vector2<f32>| -> Vec<usize> {
+            bricks.iter().cloned()
+                .filter(|&i| {
+                    let obj = level.brick(i).object();
+                    collision::sweep_circle_aabb(
+                        center, RADIUS, motion, obj.position, obj.size)
+                        .is_some()
+                })
+                .collect()
+        };
+        let all: Vec<usize> = (0..level.bricks_iter().count()).collect();
+        let mut rng = rand::XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut nearby = Vec::new();
+        for _ in 0..1000 {
+            let center: cgmath::Vector2<f32> = vec2(
+                rng.gen_range(-20.0, 820.0), rng.gen_range(-20.0, 320.0));
+            let motion: cgmath::Vector2<f32> = vec2(
+                rng.gen_range(-30.0, 30.0), rng.gen_range(-30.0, 30.0));
+            let end = center + motion;
+            let reach = vec2(RADIUS, RADIUS);
+            level.bricks_near(
+                vec2(center.x.min(end.x), center.y.min(end.y)) - reach,
+                vec2(center.x.max(end.x), center.y.max(end.y)) + reach,
+                &mut nearby);
+            assert_eq!(hits(&nearby, center, motion),
+                       hits(&all, center, motion));
+        }
+    }
+
+    #[test]
+    fn brick_at_a_point() {
+        // Two 400 by 150 bricks, with the top right one missing.
+        let mut level = parse("2 0\n2 2").unwrap();
+        let mut found = Vec::new();
+        let at = |level: &GameLevel, x, y, found: &mut Vec<usize>| {
+            level.is_brick_at(cgmath::vec2(x, y), found)
+        };
+        assert!(at(&level, 100.0, 100.0, &mut found));
+        assert!(!at(&level, 500.0, 100.0, &mut found));
+        assert!(at(&level, 500.0, 200.0, &mut found));
+        // Edges don't count, even where two bricks meet.
+        assert!(!at(&level, 400.0, 200.0, &mut found));
+        assert!(!at(&level, 100.0, 300.0, &mut found));
+
+        level.brick_mut(0).smash();
+        assert!(!at(&level, 100.0, 100.0, &mut found));
+    }
+}
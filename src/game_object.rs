@@ -12,36 +12,41 @@
 //
 // The original code was modified by Adrian Chan in order to port it to Rust.
 
+use std::rc::Rc;
+
 use cgmath;
-use gfx;
-use num_traits;
 
+use animation::{Animation, AnimationClip};
 use collision;
-use renderer;
-use texture;
-
-
-pub fn initial_player_size() -> cgmath::Vector2<f32> {
-    cgmath::vec2(100.0, 20.0)
-}
+use config::GameConfig;
 
 
+// Game objects don't own any graphics resources, so the simulation can run
+// without a graphics context (e.g. in tests). The sprite is just the name of
+// a texture, which the renderer looks up when it draws the object.
+// If the object has an animation, the sprite is kept up to date with the
+// animation's current frame.
 #[derive(Clone)]
-pub struct GameObject<R: gfx::Resources> {
+pub struct GameObject {
     pub position: cgmath::Vector2<f32>,
+    // Where the object was before the last update. The renderer blends
+    // between this and position, since it usually draws at some point
+    // between two updates.
+    pub previous_position: cgmath::Vector2<f32>,
     pub size: cgmath::Vector2<f32>,
     pub velocity: cgmath::Vector2<f32>,
     pub colour: cgmath::Vector3<f32>,
     pub rotation: f32,
     pub is_solid: bool,
     pub is_destroyed: bool,
-    pub sprite: texture::Texture2D<R>,
+    pub sprite: String,
+    pub animation: Option<Animation>,
 }
 
-impl <R: gfx::Resources> GameObject<R> {
+impl GameObject {
     pub fn new(
         position: cgmath::Vector2<f32>, size: cgmath::Vector2<f32>,
-        sprite: &texture::Texture2D<R>,
+        sprite: &str,
         colour: cgmath::Vector3<f32>)
     -> Self
     {
@@ -49,51 +54,91 @@ impl <R: gfx::Resources> GameObject<R> {
 
         Self {
             position,
+            previous_position: position,
             size,
             velocity: vec2(0.0, 0.0),
             colour,
             rotation: 0.0,
             is_solid: false,
             is_destroyed: false,
-            sprite: sprite.clone(),
+            sprite: sprite.into(),
+            animation: None,
         }
     }
 
-    pub fn draw<C: gfx::CommandBuffer<R>>(
-        &self,
-        renderer: &mut renderer::SpriteRenderer<R>,
-        encoder: &mut gfx::Encoder<R, C>)
-    {
-        renderer.draw_sprite(
-            &self.sprite,
-            self.position,
-            self.size,
-            self.rotation,
-            self.colour,
-            encoder);
+    // Call before each update, so that we know where the object moved from.
+    // Also call it after an object jumps somewhere, rather than moving
+    // there, so that it isn't drawn part way between the two.
+    pub fn store_position(&mut self) {
+        self.previous_position = self.position;
     }
-}
 
+    // alpha is how far we are between the last update and the next, from 0
+    // to 1.
+    pub fn interpolated_position(&self, alpha: f32) -> cgmath::Vector2<f32> {
+        self.previous_position + (self.position - self.previous_position) * alpha
+    }
 
-pub struct BallObject<R: gfx::Resources> {
-    obj: GameObject<R>,
-    radius: f32,
-    stuck: bool,
-}
+    // Start playing a clip from the beginning, replacing any animation that
+    // was already playing.
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        let animation = Animation::new(clip);
+        self.sprite = animation.sprite().into();
+        self.animation = Some(animation);
+    }
 
-impl <R: gfx::Resources> BallObject<R> {
-    pub fn initial_velocity() -> cgmath::Vector2<f32> {
-        cgmath::vec2(100.0, -350.0)
+    // Every sprite the object could be drawn with: the one it has now, and
+    // all the frames of its animation.
+    pub fn sprite_names(&self) -> Vec<String> {
+        let mut names = vec![self.sprite.clone()];
+        if let Some(ref animation) = self.animation {
+            names.extend(animation.clip().sprite_names());
+        }
+        names
     }
 
-    pub fn initial_radius() -> f32 {
-        12.5
+    pub fn is_animating(&self) -> bool {
+        self.animation.as_ref().map_or(false, |a| !a.is_finished())
     }
 
+    // Destroyed objects are still drawn until they've finished animating,
+    // e.g. so that bricks can be seen breaking.
+    pub fn is_visible(&self) -> bool {
+        !self.is_destroyed || self.is_animating()
+    }
+
+    // Everything apart from the ball is a box.
+    pub fn shape(&self) -> collision::Shape {
+        collision::Aabb::new(self.position, self.size).into()
+    }
+
+    pub fn animate(&mut self, delta_time: f32) {
+        if let Some(ref mut animation) = self.animation {
+            animation.update(delta_time);
+            if self.sprite != animation.sprite() {
+                self.sprite = animation.sprite().into();
+            }
+        }
+    }
+}
+
+
+#[derive(Clone)]
+pub struct BallObject {
+    obj: GameObject,
+    radius: f32,
+    stuck: bool,
+    // Sticks to the paddle every time it hits it.
+    sticky: bool,
+    // Goes straight through non-solid bricks.
+    pass_through: bool,
+}
+
+impl BallObject {
     pub fn new(
         position: cgmath::Vector2<f32>, radius: f32,
         velocity: cgmath::Vector2<f32>,
-        sprite: &texture::Texture2D<R>,
+        sprite: &str,
         colour: cgmath::Vector3<f32>)
     -> Self
     {
@@ -107,6 +152,8 @@ impl <R: gfx::Resources> BallObject<R> {
             obj,
             radius,
             stuck: true,
+            sticky: false,
+            pass_through: false,
         }
     }
 
@@ -116,26 +163,52 @@ impl <R: gfx::Resources> BallObject<R> {
         velocity: cgmath::Vector2<f32>)
     {
         self.obj.position = position;
+        self.obj.store_position();
         self.obj.velocity = velocity;
         self.stuck = true;
+        self.sticky = false;
+        self.pass_through = false;
     }
 
-    pub fn do_move(&mut self, delta_time: f32, window_width: f32) {
-        if !self.stuck {
-            self.obj.position += self.obj.velocity * delta_time;
-            if self.obj.position.x <= 0.0 {
-                self.obj.velocity.x = -self.obj.velocity.x;
-                self.obj.position.x = 0.0;
-            }
-            else if self.obj.position.x + self.obj.size.x >= window_width {
-                self.obj.velocity.x = -self.obj.velocity.x;
-                self.obj.position.x = window_width - self.obj.size.x;
-            }
-            if self.obj.position.y <= 0.0 {
-                self.obj.velocity.y = -self.obj.velocity.y;
-                self.obj.position.y = 0.0;
-            }
+    // Move the ball without checking for collisions. Game::move_ball works
+    // out how far it can go before it hits something.
+    pub fn translate(&mut self, offset: cgmath::Vector2<f32>) {
+        self.obj.position += offset;
+    }
+
+    pub fn center(&self) -> cgmath::Vector2<f32> {
+        use self::cgmath::ElementWise;
+
+        self.obj.position.add_element_wise(self.radius)
+    }
+
+    // The walls the ball would hit if it moved by motion. There's no wall
+    // at the bottom of the screen, that's how the ball gets lost.
+    pub fn wall_contacts(&self, motion: cgmath::Vector2<f32>, window_width: f32)
+        -> Vec<collision::Contact>
+    {
+        use self::cgmath::vec2;
+
+        let position = self.obj.position;
+        let mut walls = Vec::with_capacity(2);
+        if motion.x < 0.0 {
+            walls.push((-position.x / motion.x, vec2(1.0, 0.0)));
         }
+        else if motion.x > 0.0 {
+            let right = window_width - self.obj.size.x;
+            walls.push(((right - position.x) / motion.x, vec2(-1.0, 0.0)));
+        }
+        if motion.y < 0.0 {
+            walls.push((-position.y / motion.y, vec2(0.0, 1.0)));
+        }
+
+        walls.into_iter()
+            // Already past a wall means we hit it straight away.
+            .map(|(time, normal)| {
+                collision::Contact { time: time.max(0.0), normal }
+            })
+            .filter(|contact| contact.time <= 1.0)
+            .collect()
     }
 
     pub fn is_stuck(&self) -> bool {
@@ -146,6 +219,34 @@ impl <R: gfx::Resources> BallObject<R> {
         self.stuck = false;
     }
 
+    pub fn is_sticky(&self) -> bool {
+        self.sticky
+    }
+
+    pub fn set_sticky(&mut self, sticky: bool) {
+        self.sticky = sticky;
+    }
+
+    pub fn is_pass_through(&self) -> bool {
+        self.pass_through
+    }
+
+    pub fn set_pass_through(&mut self, pass_through: bool) {
+        self.pass_through = pass_through;
+    }
+
+    pub fn set_colour(&mut self, colour: cgmath::Vector3<f32>) {
+        self.obj.colour = colour;
+    }
+
+    pub fn set_velocity(&mut self, velocity: cgmath::Vector2<f32>) {
+        self.obj.velocity = velocity;
+    }
+
+    pub fn scale_velocity(&mut self, factor: f32) {
+        self.obj.velocity *= factor;
+    }
+
     pub fn is_below(&self, y: f32) -> bool {
         self.obj.position.y > y
     }
@@ -154,75 +255,96 @@ impl <R: gfx::Resources> BallObject<R> {
         self.obj.position.x += dx;
     }
 
-    pub fn draw<C: gfx::CommandBuffer<R>>(
-        &self,
-        renderer: &mut renderer::SpriteRenderer<R>,
-        encoder: &mut gfx::Encoder<R, C>)
-    {
-        self.obj.draw(renderer, encoder);
+    pub fn object(&self) -> &GameObject {
+        &self.obj
     }
 
-    pub fn check_collision(&self, other: &GameObject<R>) -> collision::Collision
-    {
-        use self::cgmath::{ElementWise, InnerSpace};
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        self.obj.play(clip);
+    }
+
+    pub fn animate(&mut self, delta_time: f32) {
+        self.obj.animate(delta_time);
+    }
+
+    pub fn store_position(&mut self) {
+        self.obj.store_position();
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    // The ball stays centred where it was.
+    pub fn set_radius(&mut self, radius: f32) {
         use self::cgmath::vec2;
-        use self::collision::{Collision, Direction};
-        use self::num_traits::clamp;
-
-        let center = self.obj.position.add_element_wise(self.radius);
-        // AABB info.
-        let aabb_half_extents = other.size / 2.0;
-        let aabb_center = other.position + aabb_half_extents;
-        // Get difference vector between both centers.
-        let diff1 = center - aabb_center;
-        let clamped = vec2(
-            clamp(diff1.x, -aabb_half_extents.x, aabb_half_extents.x),
-            clamp(diff1.y, -aabb_half_extents.y, aabb_half_extents.y));
-        // Add clamped value to AABB_center and we get the value of box closest
-        // to circle.
-        let closest = aabb_center + clamped;
-        // Retrieve vector between center circle and closest point AABB and check
-        // if length <= radius.
-        let diff2 = closest - center;
-        if diff2.magnitude() <= self.radius {
-            let direction = Direction::nearest_from(&diff2);
-            Collision::Yes(direction, diff2)
-        }
-        else {
-            Collision::No
-        }
+
+        let center = self.center();
+        self.radius = radius;
+        self.obj.size = vec2(radius * 2.0, radius * 2.0);
+        self.obj.position = center - vec2(radius, radius);
     }
 
-    pub fn rebound_brick(
-        &mut self,
-        direction: collision::Direction,
-        difference: cgmath::Vector2<f32>)
-    {
-        use self::collision::Direction;
-
-        match direction {
-            Direction::Left | Direction::Right => {
-                self.obj.velocity.x = -self.obj.velocity.x;
-                let penetration = self.radius - difference.x.abs();
-                if let Direction::Left = direction {
-                    self.obj.position.x += penetration;
-                } else {
-                    self.obj.position.x -= penetration;
-                }
-            },
-            Direction::Up | Direction::Down => {
-                self.obj.velocity.y = -self.obj.velocity.y;
-                let penetration = self.radius - difference.y.abs();
-                if let Direction::Up = direction {
-                    self.obj.position.y -= penetration;
-                } else {
-                    self.obj.position.y += penetration;
-                }
-            }
+    pub fn shape(&self) -> collision::Shape {
+        collision::Circle { center: self.center(), radius: self.radius }.into()
+    }
+
+    // Bounce off everything the ball is touching, see collision::bounce.
+    pub fn bounce(&mut self, normals: &[cgmath::Vector2<f32>]) {
+        self.obj.velocity = collision::bounce(self.obj.velocity, normals);
+    }
+
+    // A copy of the ball, heading off at an angle (in radians) to this one
+    // at the same speed. The copy is never stuck to the paddle.
+    pub fn split(&self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        let velocity = self.obj.velocity;
+        let mut ball = self.clone();
+        ball.obj.velocity = cgmath::vec2(
+            velocity.x * cos - velocity.y * sin,
+            velocity.x * sin + velocity.y * cos);
+        ball.stuck = false;
+        ball
+    }
+
+    // Bounce two balls off each other, if they're touching and heading
+    // towards each other. They're the same weight, so an elastic collision
+    // just swaps the parts of their velocities that point along the line
+    // between them. Both are moved apart by the same amount so they stop
+    // overlapping. Returns true if they bounced.
+    //
+    // Balls that overlap but are already moving apart are left alone. That
+    // includes balls that have just been split off each other, which start
+    // in the same place.
+    //
+    // Being pushed apart can put a ball into a wall or a brick. That's left
+    // for the next Game::move_ball to sort out, since a sweep that starts
+    // inside something bounces straight back out of it.
+    pub fn collide(&mut self, other: &mut BallObject) -> bool {
+        use self::cgmath::InnerSpace;
+
+        if self.center() == other.center() {
+            return false;
+        }
+        let overlap = match collision::overlap(&self.shape(), &other.shape()) {
+            Some(overlap) => overlap,
+            None => return false,
+        };
+        let closing = (self.obj.velocity - other.obj.velocity)
+            .dot(overlap.normal);
+        if closing >= 0.0 {
+            return false;
         }
+
+        let push = overlap.normal * (overlap.depth / 2.0);
+        self.obj.position += push;
+        other.obj.position -= push;
+        self.obj.velocity -= overlap.normal * closing;
+        other.obj.velocity += overlap.normal * closing;
+        true
     }
 
-    pub fn rebound_paddle(&mut self, paddle: &GameObject<R>) {
+    pub fn rebound_paddle(&mut self, paddle: &GameObject, config: &GameConfig) {
         use self::cgmath::InnerSpace;
 
         // New ball velocity depends on distance from the center of the
@@ -231,11 +353,79 @@ impl <R: gfx::Resources> BallObject<R> {
         let distance = (self.obj.position.x + self.radius) - center;
         let percentage = distance / (paddle.size.x / 2.0);
 
-        const STRENGTH: f32 = 2.0;
+        let strength = config.paddle.strength;
         // Overall speed is preserved.
         let speed = self.obj.velocity.magnitude();
-        self.obj.velocity.x = Self::initial_velocity().x * percentage * STRENGTH;
+        self.obj.velocity.x = config.ball.velocity[0] * percentage * strength;
         self.obj.velocity.y = -1.0 * self.obj.velocity.y.abs();
         self.obj.velocity = self.obj.velocity.normalize() * speed;
+        self.stuck = self.sticky;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::{vec2, vec3};
+
+    type Vec2 = cgmath::Vector2<f32>;
+
+    // A moving ball with a radius of 10.
+    fn ball(center: Vec2, velocity: Vec2) -> BallObject {
+        let mut ball = BallObject::new(
+            center - vec2(10.0, 10.0), 10.0, velocity, "ball",
+            vec3(1.0, 1.0, 1.0));
+        ball.release();
+        ball
+    }
+
+    #[test]
+    fn head_on_balls_swap_velocities() {
+        let mut a = ball(vec2(0.0, 0.0), vec2(100.0, 0.0));
+        let mut b = ball(vec2(15.0, 0.0), vec2(-100.0, 0.0));
+        assert!(a.collide(&mut b));
+        assert_eq!(a.object().velocity, vec2(-100.0, 0.0));
+        assert_eq!(b.object().velocity, vec2(100.0, 0.0));
+        // Pushed apart by half the overlap each, so they're just touching.
+        assert_eq!(a.center(), vec2(-2.5, 0.0));
+        assert_eq!(b.center(), vec2(17.5, 0.0));
+    }
+
+    #[test]
+    fn only_the_part_along_the_line_between_them_swaps() {
+        let mut a = ball(vec2(0.0, 0.0), vec2(100.0, 50.0));
+        let mut b = ball(vec2(15.0, 0.0), vec2(0.0, 0.0));
+        assert!(a.collide(&mut b));
+        assert_eq!(a.object().velocity, vec2(0.0, 50.0));
+        assert_eq!(b.object().velocity, vec2(100.0, 0.0));
+    }
+
+    #[test]
+    fn separating_balls_are_left_alone() {
+        let mut a = ball(vec2(0.0, 0.0), vec2(-100.0, 0.0));
+        let mut b = ball(vec2(15.0, 0.0), vec2(100.0, 0.0));
+        assert!(!a.collide(&mut b));
+        assert_eq!(a.object().velocity, vec2(-100.0, 0.0));
+        assert_eq!(a.center(), vec2(0.0, 0.0));
+        assert_eq!(b.center(), vec2(15.0, 0.0));
+    }
+
+    #[test]
+    fn balls_in_the_same_place_are_left_alone() {
+        // E.g. straight after a split.
+        let mut a = ball(vec2(0.0, 0.0), vec2(100.0, 0.0));
+        let mut b = a.split(0.5);
+        assert!(!a.collide(&mut b));
+        assert_eq!(a.center(), b.center());
+    }
+
+    #[test]
+    fn balls_that_arent_touching_are_left_alone() {
+        let mut a = ball(vec2(0.0, 0.0), vec2(100.0, 0.0));
+        let mut b = ball(vec2(25.0, 0.0), vec2(-100.0, 0.0));
+        assert!(!a.collide(&mut b));
+        assert_eq!(a.object().velocity, vec2(100.0, 0.0));
     }
 }
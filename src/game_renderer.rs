@@ -0,0 +1,485 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// In the original code the Game class both simulated and drew everything.
+// Here the drawing half lives on its own: the GameRenderer owns all of the
+// graphics resources, and draws whatever state the Game is in. Game objects
+// only know the name of their sprite, so we look up the actual texture here.
+
+use cgmath;
+use gfx;
+
+use audio::AudioManager;
+use errors::*;
+use game::{Game, GameState};
+use game_object::GameObject;
+use input::Action;
+use particle_renderer::ParticleRenderer;
+use post_processor::PostProcessor;
+use renderer;
+use resource_manager::ResourceManager;
+use text_renderer::TextRenderer;
+use texture;
+
+
+pub struct GameRenderer<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> {
+    height: i32,
+    width: i32,
+    resources: ResourceManager<F, R>,
+    sprite_renderer: renderer::SpriteRenderer<R>,
+    particle_renderer: ParticleRenderer<R>,
+    post_processor: PostProcessor<R>,
+    // Text is drawn straight to the framebuffer after post processing, so
+    // that it stays readable whatever effects are going on. That needs its
+    // own sprite renderer, since the other one draws into the scene.
+    hud_renderer: renderer::SpriteRenderer<R>,
+    text_renderer: TextRenderer<R>,
+    // How far between the last two game updates we're drawing. Set at the
+    // start of each render.
+    alpha: f32,
+}
+
+impl<F: gfx::traits::FactoryExt<R> + Clone, R: gfx::Resources> GameRenderer<F, R> {
+    // The scene is multisampled if msaa_samples is more than 1.
+    // Since the renderer owns the resource manager, this is also where the
+    // sounds are loaded into the audio manager. Fails if anything the game
+    // could draw hasn't been loaded.
+    pub fn new(
+        fb_width: i32, fb_height: i32, msaa_samples: u8,
+        mut factory: F, fb: renderer::RenderTargetView<R>,
+        audio: AudioManager, game: &Game
+        )-> Result<Self>
+    {
+        let mut resources = ResourceManager::new(factory.clone());
+        resources.set_audio(audio);
+        resources.load_shader(
+            &"assets/shaders/sprite.vs", &"assets/shaders/sprite.fs", None,
+            "sprite".into())?;
+        resources.load_shader(
+            &"assets/shaders/particle.vs", &"assets/shaders/particle.fs", None,
+            "particle".into())?;
+        resources.load_shader(
+            &"assets/shaders/post_processing.vs",
+            &"assets/shaders/post_processing.fs", None,
+            "post_processing".into())?;
+        resources.load_shader(
+            &"assets/shaders/resolve.vs", &"assets/shaders/resolve.fs", None,
+            "resolve".into())?;
+        // Textures. The background is big enough to want a texture of its
+        // own, and the particle renderer needs a whole texture. Everything
+        // else is small and drawn often, so it all gets packed into an atlas
+        // so the bricks, paddle and ball can share one draw call.
+        resources.load_texture(
+            &"assets/textures/background.jpg",
+            "background".into())?;
+        resources.load_texture(
+            &"assets/textures/particle.png",
+            "particle".into())?;
+        resources.load_atlas_image(
+            &"assets/textures/awesomeface.png",
+            "face".into())?;
+        resources.load_atlas_image(
+            &"assets/textures/block.png",
+            "block".into())?;
+        resources.load_atlas_image(
+            &"assets/textures/block_solid.png",
+            "block_solid".into())?;
+        resources.load_atlas_image(
+            &"assets/textures/paddle.png",
+            "paddle".into())?;
+        // Animations. Each frame of a sheet becomes a sprite of its own.
+        resources.load_sprite_sheet(
+            &"assets/textures/ball_spin.png", 128, 128,
+            "ball_spin".into())?;
+        resources.load_sprite_sheet(
+            &"assets/textures/brick_break.png", 128, 128,
+            "brick_break".into())?;
+        resources.load_sprite_sheet(
+            &"assets/textures/powerup_shimmer.png", 128, 128,
+            "powerup_shimmer".into())?;
+        resources.build_atlases()?;
+        // Better to find out now than part way through drawing a frame.
+        for name in game.sprite_names() {
+            if resources.sprite(&name).is_none() {
+                bail!("no sprite resource for {}", name);
+            }
+        }
+        let particle = &game.particles().config().sprite;
+        if resources.texture(particle).is_none() {
+            bail!("no texture resource for {}", particle);
+        }
+        // Fonts.
+        resources.load_font(
+            &"assets/fonts/DejaVuSansMono.ttf", 24.0,
+            "hud".into())?;
+        // Sounds.
+        resources.load_sound(
+            &"assets/audio/brick.wav",
+            "brick".into())?;
+        resources.load_sound(
+            &"assets/audio/solid.wav",
+            "solid".into())?;
+        resources.load_sound(
+            &"assets/audio/paddle.wav",
+            "paddle".into())?;
+        resources.load_sound(
+            &"assets/audio/powerup.wav",
+            "powerup".into())?;
+        resources.load_sound(
+            &"assets/audio/music.wav",
+            "music".into())?;
+
+        // left, right, bottom, top, near, far.
+        // Note that bottom and top are "backwards", with y increasing down
+        // the screen.
+        let projection = cgmath::ortho(
+            0.0, fb_width as f32,
+            fb_height as f32, 0.0,
+            -1.0, 1.0);
+
+        // Everything is drawn into the post processor's scene, rather than
+        // straight to the framebuffer.
+        let post_processor = PostProcessor::new(
+            resources.shader("post_processing").unwrap(),
+            resources.shader("resolve"),
+            fb_width as u16, fb_height as u16, msaa_samples,
+            &mut factory,
+            fb.clone())?;
+        let scene = post_processor.scene_target().clone();
+
+        let sprite_renderer = renderer::SpriteRenderer::new(
+            resources.shader("sprite").unwrap(),
+            &projection,
+            &mut factory,
+            scene.clone())?;
+
+        let particle_renderer = ParticleRenderer::new(
+            resources.shader("particle").unwrap(),
+            &projection,
+            &mut factory,
+            scene)?;
+
+        let hud_renderer = renderer::SpriteRenderer::new(
+            resources.shader("sprite").unwrap(),
+            &projection,
+            &mut factory,
+            fb)?;
+        let text_renderer = TextRenderer::new(
+            resources.font("hud").unwrap().clone());
+
+        Ok(Self {
+            height: fb_height,
+            width: fb_width,
+            resources,
+            sprite_renderer,
+            particle_renderer,
+            post_processor,
+            hud_renderer,
+            text_renderer,
+            alpha: 1.0,
+        })
+    }
+
+    // alpha is how far we are between the game's last update and its next
+    // one, from 0 to 1. Moving objects are drawn that far between where they
+    // were and where they are now. The particles aren't -- they're small and
+    // short-lived enough that nobody will notice.
+    pub fn render<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, alpha: f32, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.alpha = alpha;
+        self.post_processor.begin_render(encoder);
+        match *game.state() {
+            GameState::Active => self.render_active(game, encoder),
+            GameState::Menu => self.render_menu(game, encoder),
+            GameState::Win => self.render_win(game, encoder),
+            GameState::Lose => self.render_lose(game, encoder),
+        }
+        // Sprites are only queued up when they're drawn, so make sure they
+        // have all gone out before the scene is finished.
+        self.sprite_renderer.flush(encoder);
+        self.post_processor.end_render(encoder);
+        self.post_processor.render(game.screen_effects(), game.time(), encoder);
+
+        match *game.state() {
+            GameState::Active => self.render_active_text(game, encoder),
+            GameState::Menu => self.render_menu_text(game, encoder),
+            GameState::Win => self.render_win_text(game, encoder),
+            GameState::Lose => self.render_lose_text(game, encoder),
+        }
+        self.hud_renderer.flush(encoder);
+    }
+
+    fn render_active<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_background(game, base_colour!(), encoder);
+        self.draw_scene(game, encoder);
+    }
+
+    // The menu shows the currently selected level behind everything else, so
+    // the player can see which one they're picking.
+    fn render_menu<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_background(game, cgmath::vec3(0.5, 0.5, 0.5), encoder);
+        self.draw_scene(game, encoder);
+    }
+
+    fn render_win<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_background(game, cgmath::vec3(0.6, 1.0, 0.6), encoder);
+        self.draw_object(game.player(), encoder);
+        for ball in game.balls() {
+            self.draw_object(ball.object(), encoder);
+        }
+    }
+
+    fn render_lose<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_background(game, cgmath::vec3(1.0, 0.3, 0.3), encoder);
+    }
+
+    fn render_active_text<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_hud(game, encoder);
+        if game.is_paused() {
+            let y = self.height as f32 / 2.0;
+            let text = format!("Press {} to carry on",
+                               key_name(game, Action::Pause));
+            self.draw_centred_text(
+                "PAUSED", y - 30.0, 1.0, base_colour!(), encoder);
+            self.draw_centred_text(&text, y, 0.75, base_colour!(), encoder);
+        }
+    }
+
+    fn render_menu_text<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let y = self.height as f32 / 2.0;
+        let confirm = key_name(game, Action::Confirm);
+        let start = format!("Press {} to start", confirm);
+        let select = format!(
+            "Press {} or {} to select level",
+            key_name(game, Action::MenuUp), key_name(game, Action::MenuDown));
+        self.draw_hud(game, encoder);
+        self.draw_centred_text(&start, y, 1.0, base_colour!(), encoder);
+        self.draw_centred_text(
+            &select, y + 30.0, 0.75, base_colour!(), encoder);
+        self.draw_high_scores(game, y + 80.0, encoder);
+    }
+
+    fn render_win_text<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let y = self.height as f32 / 2.0;
+        self.draw_centred_text(
+            "You WON!!!", y - 30.0, 1.0, cgmath::vec3(0.0, 1.0, 0.0), encoder);
+        self.draw_final_score(game, y + 30.0, encoder);
+        let confirm = key_name(game, Action::Confirm);
+        let text = format!(
+            "Press {} to return to the menu or ESC to quit", confirm);
+        self.draw_centred_text(
+            &text, y, 1.0, cgmath::vec3(1.0, 1.0, 0.0), encoder);
+    }
+
+    fn render_lose_text<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let y = self.height as f32 / 2.0;
+        self.draw_centred_text(
+            "GAME OVER", y - 30.0, 1.0, cgmath::vec3(1.0, 0.2, 0.2), encoder);
+        self.draw_final_score(game, y + 30.0, encoder);
+        let confirm = key_name(game, Action::Confirm);
+        let text = format!(
+            "Press {} to return to the menu or ESC to quit", confirm);
+        self.draw_centred_text(
+            &text, y, 1.0, cgmath::vec3(1.0, 1.0, 0.0), encoder);
+    }
+
+    fn draw_final_score<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, y: f32, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let score = game.score();
+        let text = format!(
+            "Score: {}  Best combo: {}", score.points(), score.best_combo());
+        self.draw_centred_text(&text, y, 1.0, base_colour!(), encoder);
+        if let Some(rank) = game.high_score_rank() {
+            let text = format!("New high score! #{}", rank);
+            self.draw_centred_text(
+                &text, y + 30.0, 1.0, cgmath::vec3(1.0, 1.0, 0.0), encoder);
+        }
+    }
+
+    // The top few entries of the high score table, one per line.
+    fn draw_high_scores<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, y: f32, encoder: &mut gfx::Encoder<R, C>)
+    {
+        const SHOWN: usize = 5;
+        const SCALE: f32 = 0.75;
+
+        let entries = game.high_scores().entries();
+        if entries.is_empty() {
+            return;
+        }
+        let line_height = self.text_renderer.font().line_height() * SCALE;
+        self.draw_centred_text(
+            "High scores", y, SCALE, cgmath::vec3(1.0, 1.0, 0.0), encoder);
+        for (i, entry) in entries.iter().take(SHOWN).enumerate() {
+            let text = format!(
+                "{}. {:<12} {:>7}  level {}  {}",
+                i + 1, entry.name, entry.score, entry.level, entry.date());
+            let line_y = y + line_height * (i + 1) as f32;
+            self.draw_centred_text(&text, line_y, SCALE, base_colour!(), encoder);
+        }
+    }
+
+    // Lives in the top-left, the score in the middle, and the level in the
+    // top-right.
+    fn draw_hud<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        const MARGIN: f32 = 5.0;
+        let lives = format!("Lives: {}", game.lives());
+        self.text_renderer.draw_text(
+            &mut self.hud_renderer, &lives, cgmath::vec2(MARGIN, MARGIN),
+            1.0, base_colour!(), encoder);
+
+        // Only show the multiplier once there's a combo going.
+        let score = game.score();
+        let text = if score.multiplier() > 1 {
+            format!("Score: {} x{}", score.points(), score.multiplier())
+        }
+        else {
+            format!("Score: {}", score.points())
+        };
+        self.draw_centred_text(&text, MARGIN, 1.0, base_colour!(), encoder);
+
+        let level = match game.current_level().info().name {
+            Some(ref name) => format!("Level {}: {}", game.level(), name),
+            None => format!("Level {}", game.level()),
+        };
+        let width = self.text_renderer.font().text_width(&level, 1.0);
+        self.text_renderer.draw_text(
+            &mut self.hud_renderer, &level,
+            cgmath::vec2(self.width as f32 - width - MARGIN, MARGIN),
+            1.0, base_colour!(), encoder);
+    }
+
+    // Draw text centred horizontally, with its top at y.
+    fn draw_centred_text<C: gfx::CommandBuffer<R>>(
+        &mut self, text: &str, y: f32, scale: f32,
+        colour: cgmath::Vector3<f32>, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let width = self.text_renderer.font().text_width(text, scale);
+        let x = (self.width as f32 - width) / 2.0;
+        self.text_renderer.draw_text(
+            &mut self.hud_renderer, text, cgmath::vec2(x, y), scale, colour,
+            encoder);
+    }
+
+    // Levels can choose their own background. If it isn't one we've loaded,
+    // fall back to the usual one rather than giving up.
+    fn draw_background<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, colour: cgmath::Vector3<f32>,
+        encoder: &mut gfx::Encoder<R, C>)
+    {
+        let background = &game.current_level().info().background;
+        let texture = match self.resources.texture(background) {
+            Some(texture) => texture.clone(),
+            None => self.texture("background"),
+        };
+        self.sprite_renderer.draw_sprite(
+            &texture,
+            cgmath::vec2(0.0, 0.0),
+            cgmath::vec2(self.width as f32, self.height as f32),
+            0.0,
+            colour,
+            encoder);
+        // Everything else goes on top of the background.
+        self.sprite_renderer.flush(encoder);
+    }
+
+    // The level, any powerups, the paddle and the balls with their trails.
+    fn draw_scene<C: gfx::CommandBuffer<R>>(
+        &mut self, game: &Game, encoder: &mut gfx::Encoder<R, C>)
+    {
+        for brick in game.current_level().bricks_iter()
+            .filter(|b| b.is_visible())
+        {
+            self.draw_object(brick.object(), encoder);
+        }
+
+        for powerup in game.powerups_iter().filter(|p| !p.is_destroyed()) {
+            self.draw_object(powerup.object(), encoder);
+        }
+
+        self.draw_object(game.player(), encoder);
+        // The sprite renderer batches by texture, so get everything so far
+        // drawn before the particles go on top.
+        self.sprite_renderer.flush(encoder);
+
+        let particles = game.particles();
+        let texture = self.texture(&particles.config().sprite);
+        self.particle_renderer.draw_particles(&texture, particles, encoder);
+
+        for ball in game.balls() {
+            self.draw_object(ball.object(), encoder);
+        }
+    }
+
+    fn draw_object<C: gfx::CommandBuffer<R>>(
+        &mut self, obj: &GameObject, encoder: &mut gfx::Encoder<R, C>)
+    {
+        let sprite = self.sprite(&obj.sprite);
+        self.sprite_renderer.draw_sprite_region(
+            &sprite.texture,
+            sprite.uv_rect,
+            obj.interpolated_position(self.alpha),
+            obj.size,
+            obj.rotation,
+            obj.colour,
+            encoder);
+    }
+
+    // Texture handles are reference counted, so handing out a clone is cheap
+    // and saves us from fighting the borrow checker over self. new has
+    // already checked that everything the game draws is here, so a missing
+    // one is a bug.
+    fn texture(&self, name: &str) -> texture::Texture2D<R> {
+        match self.resources.texture(name) {
+            Some(texture) => texture.clone(),
+            None => panic!("no texture resource for {}", name),
+        }
+    }
+
+    // Same as texture, but for sprites that might be part of an atlas.
+    fn sprite(&self, name: &str) -> texture::Sprite<R> {
+        match self.resources.sprite(name) {
+            Some(sprite) => sprite.clone(),
+            None => panic!("no sprite resource for {}", name),
+        }
+    }
+}
+
+// The first key bound to an action, for telling the player what to press.
+fn key_name(game: &Game, action: Action) -> String {
+    game.config().controls.keys(action).iter()
+        .find(|k| !k.trim().is_empty())
+        .map(|k| k.trim().to_uppercase())
+        .unwrap_or_else(|| "?".into())
+}
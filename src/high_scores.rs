@@ -0,0 +1,401 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code. The best runs are kept in a small TOML file
+// in the user's data directory, e.g. ~/.local/share/rust_gfx_breakout on
+// Linux:
+//
+//     version = 1
+//
+//     [[entries]]
+//     name = "adrian"
+//     score = 4250
+//     level = 3
+//     # Seconds since the Unix epoch.
+//     time = 1539000000
+//
+// A high score table isn't worth crashing over, so if the file can't be
+// understood it's moved out of the way and we start again with an empty
+// table.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dirs;
+use toml;
+
+use errors::*;
+
+
+const FORMAT_VERSION: u32 = 1;
+// How many entries are kept.
+pub const MAX_ENTRIES: usize = 10;
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    // The level the run ended on, counting from 1.
+    pub level: usize,
+    // When the run ended, in seconds since the Unix epoch.
+    pub time: u64,
+}
+
+impl HighScore {
+    // An entry for a run that has just finished.
+    pub fn new(name: &str, score: u32, level: usize) -> Self {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            name: name.into(),
+            score,
+            level,
+            time,
+        }
+    }
+
+    // The date of the run as YYYY-MM-DD (UTC).
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_from_days((self.time / 86_400) as i64);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct HighScoreFile {
+    version: u32,
+    #[serde(default)]
+    entries: Vec<HighScore>,
+}
+
+pub struct HighScores {
+    // Where the table is saved. None for a table that only lives in memory.
+    path: Option<PathBuf>,
+    entries: Vec<HighScore>,
+    // If the file was corrupted when we loaded it, this is where it was
+    // moved to.
+    backup: Option<PathBuf>,
+}
+
+impl HighScores {
+    // A table that is never saved anywhere.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: Vec::with_capacity(MAX_ENTRIES + 1),
+            backup: None,
+        }
+    }
+
+    // Where the table normally lives. Falls back to the current directory
+    // if the platform doesn't have a data directory.
+    pub fn default_path() -> PathBuf {
+        let dir = dirs::data_dir()
+            .map(|d| d.join("rust_gfx_breakout"))
+            .unwrap_or_else(|| PathBuf::from("."));
+        dir.join("high_scores.toml")
+    }
+
+    // The name to put on new entries. There's no way to type a name in
+    // the game, so use whoever is logged in.
+    pub fn player_name() -> String {
+        env::var("USER")
+            .or_else(|_| env::var("USERNAME"))
+            .unwrap_or_else(|_| "Player".into())
+    }
+
+    // A missing file is just an empty table. A file that can't be read as a
+    // high score table is renamed, and we start again with an empty table.
+    // Only fails if we can't read the file, or can't move a bad one.
+    pub fn load<P: AsRef<Path>>(path: &P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut table = Self::in_memory();
+        table.path = Some(path.to_path_buf());
+
+        let mut data = String::new();
+        match fs::File::open(path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut data)
+                    .chain_err(|| format!(
+                        "couldn't read high scores from {}", path.display()))?;
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(table);
+            },
+            Err(e) => {
+                return Err(e).chain_err(|| format!(
+                    "couldn't open high scores at {}", path.display()));
+            },
+        }
+
+        match parse(&data) {
+            Some(entries) => {
+                table.entries = entries;
+                table.tidy();
+            },
+            None => {
+                let backup = backup_path(path);
+                fs::rename(path, &backup)
+                    .chain_err(|| format!(
+                        "couldn't move corrupted high scores to {}",
+                        backup.display()))?;
+                table.backup = Some(backup);
+            },
+        }
+        Ok(table)
+    }
+
+    // Writes to a temporary file first, so a crash part way through can't
+    // leave us with half a table.
+    pub fn save(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = HighScoreFile {
+            version: FORMAT_VERSION,
+            entries: self.entries.clone(),
+        };
+        let data = toml::to_string(&file)
+            .chain_err(|| "couldn't serialise the high scores")?;
+        let temp = path.with_extension("toml.tmp");
+        {
+            let mut out = fs::File::create(&temp)?;
+            out.write_all(data.as_bytes())?;
+            out.sync_all()?;
+        }
+        fs::rename(&temp, path)
+            .chain_err(|| format!(
+                "couldn't save high scores to {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+
+    pub fn backup(&self) -> Option<&Path> {
+        self.backup.as_ref().map(|p| p.as_path())
+    }
+
+    // Would this score make it into the table?
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES
+                      || self.entries.iter().any(|e| score > e.score))
+    }
+
+    // Returns the entry's place in the table (counting from 1), or None if
+    // it wasn't good enough. Earlier entries win ties.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let index = self.entries.iter()
+            .position(|e| entry.score > e.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(index + 1)
+    }
+
+    // Best first, and no more than we're meant to keep -- just in case the
+    // file has been edited by hand.
+    fn tidy(&mut self) {
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score));
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+// None if the data isn't a high score table we understand.
+fn parse(data: &str) -> Option<Vec<HighScore>> {
+    let file: HighScoreFile = toml::from_str(data).ok()?;
+    if file.version != FORMAT_VERSION {
+        return None;
+    }
+    Some(file.entries)
+}
+
+// Pick a name that won't overwrite an earlier backup.
+fn backup_path(path: &Path) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut name = path.file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(format!(".corrupt-{}", stamp));
+    path.with_file_name(name)
+}
+
+// Convert days since 1970-01-01 to a (year, month, day) date. This is
+// Howard Hinnant's algorithm, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process;
+
+    fn entry(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.into(),
+            score,
+            level: 1,
+            time: 0,
+        }
+    }
+
+    fn scores(table: &HighScores) -> Vec<u32> {
+        table.entries().iter().map(|e| e.score).collect()
+    }
+
+    // A directory of its own, so tests don't trip over each other.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(
+            format!("breakout-scores-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn best_scores_first() {
+        let mut table = HighScores::in_memory();
+        assert_eq!(table.insert(entry("a", 100)), Some(1));
+        assert_eq!(table.insert(entry("b", 300)), Some(1));
+        assert_eq!(table.insert(entry("c", 200)), Some(2));
+        // Ties go to whoever got there first.
+        assert_eq!(table.insert(entry("d", 200)), Some(3));
+        assert_eq!(scores(&table), vec![300, 200, 200, 100]);
+        assert_eq!(table.entries()[1].name, "c");
+    }
+
+    #[test]
+    fn only_the_top_entries_are_kept() {
+        let mut table = HighScores::in_memory();
+        for score in 1..(MAX_ENTRIES as u32 + 1) {
+            table.insert(entry("a", score * 10));
+        }
+        assert_eq!(table.entries().len(), MAX_ENTRIES);
+
+        // Not good enough to get in.
+        assert!(!table.qualifies(10));
+        assert_eq!(table.insert(entry("b", 5)), None);
+        // Pushes the lowest one out.
+        assert!(table.qualifies(15));
+        assert_eq!(table.insert(entry("c", 15)), Some(MAX_ENTRIES));
+        assert_eq!(table.entries().len(), MAX_ENTRIES);
+        assert_eq!(*scores(&table).last().unwrap(), 15);
+        assert_eq!(table.insert(entry("d", 1000)), Some(1));
+        assert_eq!(table.entries().len(), MAX_ENTRIES);
+        assert_eq!(*scores(&table).last().unwrap(), 20);
+    }
+
+    #[test]
+    fn nothing_scores_nothing() {
+        let mut table = HighScores::in_memory();
+        assert!(!table.qualifies(0));
+        assert_eq!(table.insert(entry("a", 0)), None);
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let dir = temp_dir("saved");
+        let path = dir.join("high_scores.toml");
+        let mut table = HighScores::load(&path).unwrap();
+        assert!(table.entries().is_empty());
+        table.insert(entry("a", 100));
+        table.insert(entry("b", 200));
+        table.save().unwrap();
+
+        let loaded = HighScores::load(&path).unwrap();
+        assert_eq!(loaded.entries(), table.entries());
+        assert_eq!(loaded.backup(), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hand_edited_tables_are_tidied() {
+        let dir = temp_dir("tidied");
+        let path = dir.join("high_scores.toml");
+        let mut data = String::from("version = 1\n");
+        for score in 0..(MAX_ENTRIES as u32 + 5) {
+            data.push_str(&format!(
+                "[[entries]]\nname = \"a\"\nscore = {}\nlevel = 1\ntime = 0\n",
+                score));
+        }
+        fs::write(&path, data).unwrap();
+
+        let table = HighScores::load(&path).unwrap();
+        assert_eq!(table.entries().len(), MAX_ENTRIES);
+        assert_eq!(table.entries()[0].score, MAX_ENTRIES as u32 + 4);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_file_is_moved_out_of_the_way() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("high_scores.toml");
+        for data in &["this isn't toml [", "version = 99\n"] {
+            fs::write(&path, data).unwrap();
+
+            let table = HighScores::load(&path).unwrap();
+            assert!(table.entries().is_empty());
+            assert!(!path.exists());
+            // The old file is kept, just in case.
+            let backup = table.backup().unwrap();
+            assert_eq!(backup.parent(), Some(dir.as_path()));
+            assert_eq!(fs::read_to_string(backup).unwrap(), *data);
+            fs::remove_file(backup).unwrap();
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(10_957), (2000, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+
+        let mut score = entry("a", 1);
+        score.time = 1_539_000_000;
+        assert_eq!(score.date(), "2018-10-08");
+    }
+}
@@ -0,0 +1,259 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code, which read particular keys straight out of
+// GLFW. The game doesn't care about keys, only what the player wants to do,
+// so it works in terms of actions instead. Whatever is hosting the game turns
+// the keys it knows about into actions using the bindings from the config.
+//
+// Keys are bound by name, so the game doesn't need to know anything about the
+// windowing library. The names are the ones in KEY_NAMES, and it's up to the
+// host to tell us its keys by those names. Names don't care about case.
+
+use std::collections::{HashMap, HashSet};
+
+use config::ControlsConfig;
+
+
+// Every key that can be bound to an action.
+pub const KEY_NAMES: &[&str] = &[
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+    "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+    "Kp0", "Kp1", "Kp2", "Kp3", "Kp4", "Kp5", "Kp6", "Kp7", "Kp8", "Kp9",
+    "KpEnter",
+    "Space", "Enter", "Tab", "Backspace",
+    "Left", "Right", "Up", "Down",
+    "LeftShift", "RightShift", "LeftControl", "RightControl",
+    "LeftAlt", "RightAlt",
+];
+// Hosts use this key to quit, so it can't be bound to anything else.
+pub const QUIT_KEY: &str = "Escape";
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    // Let go of any balls stuck to the paddle.
+    Launch,
+    Pause,
+    MenuUp,
+    MenuDown,
+    Confirm,
+}
+
+impl Action {
+    pub fn all() -> &'static [Action] {
+        use self::Action::*;
+        const ALL: &[Action] = &[
+            MoveLeft, MoveRight, Launch, Pause, MenuUp, MenuDown, Confirm];
+        ALL
+    }
+
+    // What the action is called in the config.
+    pub fn name(&self) -> &'static str {
+        use self::Action::*;
+
+        match *self {
+            MoveLeft => "move_left",
+            MoveRight => "move_right",
+            Launch => "launch",
+            Pause => "pause",
+            MenuUp => "menu_up",
+            MenuDown => "menu_down",
+            Confirm => "confirm",
+        }
+    }
+}
+
+
+// Which actions each key does. One key can do more than one thing, e.g. the
+// same key could be both MenuUp and Launch.
+pub struct Bindings {
+    actions: HashMap<String, Vec<Action>>,
+}
+
+impl Bindings {
+    pub fn new(controls: &ControlsConfig) -> Self {
+        let mut actions: HashMap<String, Vec<Action>> = HashMap::new();
+        for &action in Action::all() {
+            for key in controls.keys(action) {
+                let bound = actions.entry(normalise(key)).or_default();
+                if !bound.contains(&action) {
+                    bound.push(action);
+                }
+            }
+        }
+        Self {
+            actions,
+        }
+    }
+
+    // The actions that a key does. Keys that aren't bound to anything don't
+    // do anything.
+    pub fn actions(&self, key: &str) -> &[Action] {
+        self.actions.get(&normalise(key))
+            .map(|a| a.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // What the player is doing, given the names of the keys that are held
+    // down. An action stays held as long as any of its keys are.
+    pub fn input_state<I, S>(&self, keys_down: I) -> InputState
+        where I: IntoIterator<Item=S>,
+              S: AsRef<str>
+    {
+        let mut input = InputState::new();
+        for key in keys_down {
+            for &action in self.actions(key.as_ref()) {
+                input.press(action);
+            }
+        }
+        input
+    }
+}
+
+fn normalise(key: &str) -> String {
+    key.trim().to_lowercase()
+}
+
+// Is this the name of a key that can be bound?
+pub fn is_key_name(name: &str) -> bool {
+    KEY_NAMES.iter().any(|k| k.eq_ignore_ascii_case(name.trim()))
+}
+
+
+// The actions being held down at one moment. This is all the game sees of
+// the player.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputState {
+    down: HashSet<Action>,
+}
+
+impl InputState {
+    // Nothing held down.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, action: Action) {
+        self.down.insert(action);
+    }
+
+    pub fn release(&mut self, action: Action) {
+        self.down.remove(&action);
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down.contains(&action)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use config::GameConfig;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn default_bindings() {
+        let bindings = Bindings::new(&ControlsConfig::default());
+        assert_eq!(bindings.actions("A"), &[Action::MoveLeft]);
+        assert_eq!(bindings.actions("Left"), &[Action::MoveLeft]);
+        assert_eq!(bindings.actions("Space"), &[Action::Launch]);
+        assert_eq!(bindings.actions("Enter"), &[Action::Confirm]);
+        // Every action can be done somehow.
+        for &action in Action::all() {
+            assert!(KEY_NAMES.iter().any(|k| {
+                bindings.actions(k).contains(&action)
+            }), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn names_ignore_case_and_spaces() {
+        let bindings = Bindings::new(&ControlsConfig::default());
+        assert_eq!(bindings.actions(" left "), &[Action::MoveLeft]);
+        assert_eq!(bindings.actions("SPACE"), &[Action::Launch]);
+        assert!(is_key_name(" kpenter "));
+    }
+
+    #[test]
+    fn unknown_keys_do_nothing() {
+        let bindings = Bindings::new(&ControlsConfig::default());
+        assert!(bindings.actions("Return").is_empty());
+        assert!(bindings.actions("").is_empty());
+        assert!(!is_key_name("Return"));
+    }
+
+    #[test]
+    fn quit_key_is_never_bound() {
+        assert!(!is_key_name(QUIT_KEY));
+        let bindings = Bindings::new(&ControlsConfig::default());
+        assert!(bindings.actions(QUIT_KEY).is_empty());
+        // The config won't let it be bound either.
+        assert!(GameConfig::parse("[controls]\nlaunch = [\"escape\"]")
+                .is_err());
+        assert!(GameConfig::parse("[controls]\nlaunch = [\"Retrun\"]")
+                .is_err());
+    }
+
+    #[test]
+    fn duplicate_bindings_count_once() {
+        let mut controls = ControlsConfig::default();
+        controls.launch = keys(&["Space", "space", " Space"]);
+        let bindings = Bindings::new(&controls);
+        assert_eq!(bindings.actions("Space"), &[Action::Launch]);
+    }
+
+    #[test]
+    fn one_key_can_do_several_things() {
+        let mut controls = ControlsConfig::default();
+        controls.launch = keys(&["Up"]);
+        let bindings = Bindings::new(&controls);
+        assert_eq!(bindings.actions("Up"), &[Action::Launch, Action::MenuUp]);
+        assert!(bindings.actions("Space").is_empty());
+    }
+
+    #[test]
+    fn keys_held_down() {
+        let bindings = Bindings::new(&ControlsConfig::default());
+        let input = bindings.input_state(&["A", "Space", "Q", "Escape"]);
+        assert!(input.is_down(Action::MoveLeft));
+        assert!(input.is_down(Action::Launch));
+        assert!(!input.is_down(Action::MoveRight));
+        assert!(!input.is_down(Action::Pause));
+
+        // An action stays down while any of its keys are.
+        let input = bindings.input_state(&["A", "Left"]);
+        assert_eq!(input, bindings.input_state(&["Left"]));
+    }
+
+    #[test]
+    fn pressing_and_releasing() {
+        let mut input = InputState::new();
+        input.press(Action::Pause);
+        input.press(Action::Pause);
+        assert!(input.is_down(Action::Pause));
+        input.release(Action::Pause);
+        assert!(!input.is_down(Action::Pause));
+        assert_eq!(input, InputState::new());
+    }
+}
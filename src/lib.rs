@@ -1,18 +1,47 @@
 extern crate cgmath;
+extern crate dirs;
 #[macro_use]
 extern crate error_chain;
 #[macro_use]
 extern crate gfx;
 extern crate glfw;
 extern crate image;
-extern crate num_traits;
+extern crate rand;
+extern crate rodio;
+extern crate rusttype;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+
+// Use the colour of the sprite as-in.
+macro_rules! base_colour {
+    () => { cgmath::vec3(1.0, 1.0, 1.0) }
+}
 
+pub mod animation;
+pub mod atlas;
+pub mod audio;
+pub mod brick;
 pub mod collision;
+pub mod config;
 pub mod errors;
 pub mod game;
 pub use self::game::Game;
 pub mod game_level;
 pub mod game_object;
+pub mod game_renderer;
+pub use self::game_renderer::GameRenderer;
+pub mod high_scores;
+pub mod input;
+pub mod particle_generator;
+pub mod particle_renderer;
+pub mod post_processor;
+pub mod powerup;
 pub mod renderer;
 pub mod resource_manager;
+pub mod score;
+pub mod spatial_grid;
+pub mod text_renderer;
 pub mod texture;
+pub mod timestep;
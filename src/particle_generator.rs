@@ -0,0 +1,341 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// The original ParticleGenerator both updated and drew its particles. Like
+// the rest of the game objects, this version only does the simulation side --
+// the particles are drawn by particle_renderer::ParticleRenderer.
+
+use cgmath;
+use rand;
+use rand::{Rng, SeedableRng};
+
+use game_object::GameObject;
+
+
+#[derive(Clone)]
+pub struct Particle {
+    pub position: cgmath::Vector2<f32>,
+    pub velocity: cgmath::Vector2<f32>,
+    pub colour: cgmath::Vector4<f32>,
+    // Seconds left to live. Dead particles are kept around to be re-used.
+    pub life: f32,
+}
+
+impl Particle {
+    fn new() -> Self {
+        use self::cgmath::{vec2, vec4};
+
+        Self {
+            position: vec2(0.0, 0.0),
+            velocity: vec2(0.0, 0.0),
+            colour: vec4(1.0, 1.0, 1.0, 1.0),
+            life: 0.0,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.life > 0.0
+    }
+}
+
+
+#[derive(Clone)]
+pub struct ParticleConfig {
+    // The maximum number of particles alive at any one time.
+    pub amount: usize,
+    // New particles per second.
+    pub emit_rate: f32,
+    // How long each particle lives for, in seconds.
+    pub lifetime: f32,
+    // Where particles appear, relative to the position of the object they
+    // are following.
+    pub offset: cgmath::Vector2<f32>,
+    // How much alpha a particle loses per second.
+    pub fade_rate: f32,
+    // The width and height of each particle.
+    pub size: f32,
+    pub sprite: String,
+}
+
+impl Default for ParticleConfig {
+    // These match the original code, which emitted 2 particles per frame at
+    // roughly 60 frames per second.
+    fn default() -> Self {
+        Self {
+            amount: 500,
+            emit_rate: 120.0,
+            lifetime: 1.0,
+            offset: cgmath::vec2(0.0, 0.0),
+            fade_rate: 2.5,
+            size: 10.0,
+            sprite: "particle".into(),
+        }
+    }
+}
+
+
+pub struct ParticleGenerator {
+    config: ParticleConfig,
+    particles: Vec<Particle>,
+    // Where we last found a dead particle. The next dead particle is usually
+    // just after it, so we start searching from there.
+    last_used: usize,
+    // Fractions of a particle that were due but not yet emitted, carried
+    // over to the next update.
+    emit_debt: f32,
+    rng: rand::XorShiftRng,
+}
+
+impl ParticleGenerator {
+    pub fn new(config: ParticleConfig, seed: [u32; 4]) -> Self {
+        let particles = vec![Particle::new(); config.amount];
+        Self {
+            config,
+            particles,
+            last_used: 0,
+            emit_debt: 0.0,
+            rng: rand::XorShiftRng::from_seed(seed),
+        }
+    }
+
+    pub fn config(&self) -> &ParticleConfig {
+        &self.config
+    }
+
+    // E.g. when the object being followed changes size.
+    pub fn set_offset(&mut self, offset: cgmath::Vector2<f32>) {
+        self.config.offset = offset;
+    }
+
+    pub fn particles_iter(&self) -> ::std::slice::Iter<Particle> {
+        self.particles.iter()
+    }
+
+    // Emit new particles at each of the objects, then age all of the
+    // particles. Each object gets the full emit rate, so every trail looks
+    // the same however many there are.
+    pub fn update<'a, I>(&mut self, delta_time: f32, objects: I)
+        where I: IntoIterator<Item = &'a GameObject>
+    {
+        let objects: Vec<&GameObject> = objects.into_iter().collect();
+        self.emit_debt += self.config.emit_rate * delta_time;
+        while self.emit_debt >= 1.0 {
+            self.emit_debt -= 1.0;
+            for object in &objects {
+                if let Some(i) = self.first_unused_particle() {
+                    self.respawn_particle(i, object);
+                }
+            }
+        }
+
+        let fade = self.config.fade_rate * delta_time;
+        for p in self.particles.iter_mut().filter(|p| p.is_alive()) {
+            p.life -= delta_time;
+            if p.is_alive() {
+                p.position -= p.velocity * delta_time;
+                p.colour.w = (p.colour.w - fade).max(0.0);
+            }
+        }
+    }
+
+    // Kill all of the particles, e.g. when the ball is reset.
+    pub fn clear(&mut self) {
+        for p in self.particles.iter_mut() {
+            p.life = 0.0;
+        }
+        self.emit_debt = 0.0;
+    }
+
+    // Returns None if every particle is still alive. The original code
+    // overwrote the first particle in that case, but it's less noticeable to
+    // just not emit anything.
+    fn first_unused_particle(&mut self) -> Option<usize> {
+        let n = self.particles.len();
+        for i in (self.last_used..n).chain(0..self.last_used) {
+            if !self.particles[i].is_alive() {
+                self.last_used = i;
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn respawn_particle(&mut self, i: usize, object: &GameObject) {
+        use self::cgmath::vec4;
+
+        let random = self.rng.gen_range(-50, 50) as f32 / 10.0;
+        let shade = 0.5 + self.rng.gen_range(0, 100) as f32 / 100.0;
+        let particle = &mut self.particles[i];
+        particle.position =
+            object.position + self.config.offset
+            + cgmath::vec2(random, random);
+        particle.colour = vec4(shade, shade, shade, 1.0);
+        particle.life = self.config.lifetime;
+        particle.velocity = object.velocity * 0.1;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::vec2;
+
+    fn generator(amount: usize, emit_rate: f32, lifetime: f32)
+        -> ParticleGenerator
+    {
+        let config = ParticleConfig {
+            amount,
+            emit_rate,
+            lifetime,
+            fade_rate: 1.0,
+            ..ParticleConfig::default()
+        };
+        ParticleGenerator::new(config, [1, 2, 3, 4])
+    }
+
+    fn object(x: f32, y: f32) -> GameObject {
+        GameObject::new(
+            vec2(x, y), vec2(10.0, 10.0), "ball", cgmath::vec3(1.0, 1.0, 1.0))
+    }
+
+    fn alive(generator: &ParticleGenerator) -> usize {
+        generator.particles_iter().filter(|p| p.is_alive()).count()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn emits_at_the_emit_rate() {
+        let ball = object(0.0, 0.0);
+        let mut even = generator(500, 100.0, 10.0);
+        even.update(0.25, &[ball.clone()]);
+        assert_eq!(alive(&even), 25);
+        // Fractions of a particle carry over to the next update.
+        let mut uneven = generator(500, 96.0, 10.0);
+        for _ in 0..4 {
+            uneven.update(1.0 / 64.0, &[ball.clone()]);
+        }
+        assert_eq!(alive(&uneven), 6);
+    }
+
+    #[test]
+    fn nothing_to_follow_emits_nothing() {
+        let mut generator = generator(500, 100.0, 10.0);
+        generator.update(1.0, &[]);
+        assert_eq!(alive(&generator), 0);
+    }
+
+    #[test]
+    fn every_object_gets_the_full_rate() {
+        let mut generator = generator(500, 100.0, 10.0);
+        let objects = [object(0.0, 0.0), object(100.0, 0.0),
+                       object(200.0, 0.0)];
+        generator.update(0.1, &objects);
+        assert_eq!(alive(&generator), 30);
+        // Each particle starts within 5 pixels of its object.
+        for (i, object) in objects.iter().enumerate() {
+            let near = generator.particles_iter()
+                .filter(|p| p.is_alive())
+                .filter(|p| (p.position.x - object.position.x).abs() <= 5.0)
+                .count();
+            assert_eq!(near, 10, "object {}", i);
+        }
+    }
+
+    #[test]
+    fn particles_fade_and_die() {
+        let mut generator = generator(500, 10.0, 0.5);
+        generator.update(0.1, &[object(0.0, 0.0)]);
+        let particle = |g: &ParticleGenerator| {
+            g.particles_iter().find(|p| p.is_alive()).cloned()
+        };
+        let p = particle(&generator).unwrap();
+        assert!(close(p.life, 0.4));
+        assert!(close(p.colour.w, 0.9));
+
+        generator.update(0.2, &[]);
+        let p = particle(&generator).unwrap();
+        assert!(close(p.life, 0.2));
+        assert!(close(p.colour.w, 0.7));
+
+        generator.update(0.3, &[]);
+        assert_eq!(alive(&generator), 0);
+    }
+
+    #[test]
+    fn fading_stops_at_nothing() {
+        let config = ParticleConfig {
+            emit_rate: 10.0,
+            lifetime: 1.0,
+            fade_rate: 10.0,
+            ..ParticleConfig::default()
+        };
+        let mut generator = ParticleGenerator::new(config, [1, 2, 3, 4]);
+        generator.update(0.1, &[object(0.0, 0.0)]);
+        generator.update(0.2, &[]);
+        let p = generator.particles_iter().find(|p| p.is_alive()).unwrap();
+        assert_eq!(p.colour.w, 0.0);
+    }
+
+    #[test]
+    fn never_more_than_the_amount() {
+        let mut generator = generator(5, 10.0, 2.0);
+        let ball = object(0.0, 0.0);
+        generator.update(1.0, &[ball.clone()]);
+        assert_eq!(alive(&generator), 5);
+        // No room, so the particles that are there are left alone rather
+        // than being overwritten.
+        generator.update(0.5, &[ball.clone()]);
+        assert_eq!(alive(&generator), 5);
+        assert!(generator.particles_iter().all(|p| close(p.life, 0.5)));
+
+        // Once they've died, their places get used again.
+        generator.update(0.5, &[]);
+        assert_eq!(alive(&generator), 0);
+        generator.update(0.3, &[ball]);
+        assert_eq!(alive(&generator), 3);
+    }
+
+    #[test]
+    fn first_unused_particle_wraps_around() {
+        let mut generator = generator(5, 10.0, 2.0);
+        generator.update(1.0, &[object(0.0, 0.0)]);
+        assert_eq!(generator.first_unused_particle(), None);
+
+        generator.particles[1].life = 0.0;
+        generator.particles[3].life = 0.0;
+        generator.last_used = 2;
+        // Carries on from where it last found one...
+        assert_eq!(generator.first_unused_particle(), Some(3));
+        generator.particles[3].life = 1.0;
+        // ...and goes back round to the start.
+        assert_eq!(generator.first_unused_particle(), Some(1));
+    }
+
+    #[test]
+    fn clear_kills_everything() {
+        let mut generator = generator(500, 100.0, 10.0);
+        let ball = object(0.0, 0.0);
+        generator.update(0.095, &[ball.clone()]);
+        generator.clear();
+        assert_eq!(alive(&generator), 0);
+        // Including the half particle that was nearly due.
+        generator.update(0.005, &[ball]);
+        assert_eq!(alive(&generator), 0);
+    }
+}
@@ -0,0 +1,148 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Particles need their own pipeline, because they are blended differently to
+// normal sprites. In raw OpenGL the original code just changed the global
+// blend function before drawing the particles, and changed it back again
+// afterwards. With gfx the blend state is baked into the PSO, so we make a
+// second PSO instead.
+
+use cgmath;
+use gfx;
+use image;
+
+use errors::*;
+use particle_generator::ParticleGenerator;
+use renderer::{ColourFormat, RenderTargetView};
+use texture;
+
+
+// Source alpha + destination. This is what gives the particles their glow,
+// since overlapping particles add up to something brighter.
+const ADDITIVE: gfx::state::Blend = gfx::state::Blend {
+    color: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::ZeroPlus(gfx::state::BlendValue::SourceAlpha),
+        destination: gfx::state::Factor::One,
+    },
+    alpha: gfx::state::BlendChannel {
+        equation: gfx::state::Equation::Add,
+        source: gfx::state::Factor::ZeroPlus(gfx::state::BlendValue::SourceAlpha),
+        destination: gfx::state::Factor::One,
+    },
+};
+
+pub struct ParticleRenderer <R: gfx::Resources> {
+    pso_bundle: gfx::pso::bundle::Bundle<R, pipe::Data<R>>,
+}
+
+impl <R: gfx::Resources> ParticleRenderer <R> {
+    pub fn new<F: gfx::traits::FactoryExt<R>>(
+        shader: &gfx::handle::Program<R>, projection: &cgmath::Matrix4<f32>,
+        factory: &mut F, fb: RenderTargetView<R>
+        ) -> Result<Self>
+    {
+        let pso = factory.create_pipeline_from_program(
+            shader,
+            gfx::Primitive::TriangleList,
+            gfx::state::Rasterizer::new_fill().with_cull_back(),
+            pipe::new())
+            .map_err(|e| gfx::PipelineStateError::<String>::from(e))?;
+
+        // Same unit quad as the sprite renderer.
+        const VERTICES: &[Vertex] = &[
+            Vertex { pos: [0.0, 0.0], uv: [0.0, 0.0] },  // top left
+            Vertex { pos: [1.0, 0.0], uv: [1.0, 0.0] },  // top right
+            Vertex { pos: [0.0, 1.0], uv: [0.0, 1.0] },  // bottom left
+            Vertex { pos: [1.0, 1.0], uv: [1.0, 1.0] },  // bottom right
+        ];
+        const INDICES: &[u16] = &[
+            0, 3, 1,
+            0, 2, 3,
+        ];
+        let (vertex_buffer, slice) =
+            factory.create_vertex_buffer_with_slice(VERTICES, INDICES);
+
+        // See SpriteRenderer for why there's a dummy texture.
+        let sampler = factory.create_sampler(
+            gfx::texture::SamplerInfo::new(
+                gfx::texture::FilterMethod::Bilinear,
+                gfx::texture::WrapMode::Tile));
+        let default_texture = {
+            let pixel = image::Rgba { data: [255, 0, 255, 255] };
+            let img = image::RgbaImage::from_pixel(1, 1, pixel);
+            texture::Texture2D::new(img, factory)?
+        };
+
+        let data = pipe::Data {
+            vertex_buffer,
+            sprite_sampler: (default_texture.view, sampler),
+            locals: factory.create_constant_buffer(1),
+            projection: projection.clone().into(),
+            out: fb,
+        };
+
+        let pso_bundle = gfx::pso::bundle::Bundle::new(
+            slice, pso, data);
+
+        Ok(Self {
+            pso_bundle,
+        })
+    }
+
+    // Draw all of the live particles from the generator.
+    pub fn draw_particles<C: gfx::CommandBuffer<R>>(
+        &mut self,
+        texture: &texture::Texture2D<R>,
+        generator: &ParticleGenerator,
+        encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.pso_bundle.data.sprite_sampler.0 = texture.view.clone();
+        let scale = generator.config().size;
+
+        for particle in generator.particles_iter().filter(|p| p.is_alive()) {
+            let locals = Locals {
+                colour: particle.colour.into(),
+                offset: particle.position.into(),
+                scale,
+            };
+            encoder.update_constant_buffer(
+                &self.pso_bundle.data.locals, &locals);
+            self.pso_bundle.encode(encoder);
+        }
+    }
+}
+
+
+gfx_defines! {
+    vertex Vertex {
+        pos: [f32; 2] = "position",
+        uv: [f32; 2] = "texCoords",
+    }
+
+    constant Locals {
+        colour: [f32; 4] = "particleColour",
+        offset: [f32; 2] = "offset",
+        scale: f32 = "scale",
+    }
+
+    pipeline pipe {
+        vertex_buffer: gfx::VertexBuffer<Vertex> = (),
+        sprite_sampler: gfx::TextureSampler<[f32; 4]> = "sprite",
+        locals: gfx::ConstantBuffer<Locals> = "Locals",
+        projection: gfx::Global<[[f32; 4]; 4]> = "projection",
+        out: gfx::BlendTarget<ColourFormat> =
+            ("target", gfx::state::ColorMask::all(), ADDITIVE),
+    }
+}
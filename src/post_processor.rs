@@ -0,0 +1,211 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// The scene is drawn into an offscreen texture instead of the window. The
+// post processor then draws that texture onto the window with a full-screen
+// quad, applying any effects on the way.
+//
+// The original code rendered into a multisampled renderbuffer, then used
+// glBlitFramebuffer to resolve it into a normal texture. gfx doesn't give us
+// a blit, so when multisampling is turned on we do the resolve ourselves:
+// an extra full-screen pass reads the multisampled texture with texelFetch()
+// and averages the samples for each pixel.
+
+use gfx;
+
+use errors::*;
+use game::ScreenEffects;
+use renderer::{ColourFormat, RenderTargetView};
+
+
+pub struct PostProcessor <R: gfx::Resources> {
+    // Where the scene should be drawn.
+    scene_target: RenderTargetView<R>,
+    // Only used when multisampling.
+    resolve_bundle: Option<gfx::pso::bundle::Bundle<R, resolve_pipe::Data<R>>>,
+    pso_bundle: gfx::pso::bundle::Bundle<R, pipe::Data<R>>,
+}
+
+impl <R: gfx::Resources> PostProcessor <R> {
+    // If samples is more than 1 then the scene is multisampled, and the
+    // resolve shader must be given.
+    pub fn new<F: gfx::traits::FactoryExt<R>>(
+        shader: &gfx::handle::Program<R>,
+        resolve_shader: Option<&gfx::handle::Program<R>>,
+        width: u16, height: u16, samples: u8,
+        factory: &mut F, fb: RenderTargetView<R>
+        ) -> Result<Self>
+    {
+        // A quad that covers the whole screen, in normalised device
+        // coordinates.
+        const VERTICES: &[Vertex] = &[
+            Vertex { pos: [-1.0,  1.0], uv: [0.0, 1.0] },  // top left
+            Vertex { pos: [ 1.0,  1.0], uv: [1.0, 1.0] },  // top right
+            Vertex { pos: [-1.0, -1.0], uv: [0.0, 0.0] },  // bottom left
+            Vertex { pos: [ 1.0, -1.0], uv: [1.0, 0.0] },  // bottom right
+        ];
+        const INDICES: &[u16] = &[
+            0, 2, 3,
+            0, 3, 1,
+        ];
+        let (vertex_buffer, slice) =
+            factory.create_vertex_buffer_with_slice(VERTICES, INDICES);
+
+        // The texture that the effects are applied to. Wrapping is needed
+        // for the chaos effect, which scrolls the scene around.
+        let (_, scene_view, resolved_target) =
+            factory.create_render_target::<ColourFormat>(width, height)?;
+        let sampler = factory.create_sampler(
+            gfx::texture::SamplerInfo::new(
+                gfx::texture::FilterMethod::Bilinear,
+                gfx::texture::WrapMode::Tile));
+
+        let (scene_target, resolve_bundle) = if samples > 1 {
+            let resolve_shader = match resolve_shader {
+                Some(shader) => shader,
+                None => bail!("multisampling needs a resolve shader"),
+            };
+            let (ms_target, ms_view) = Self::create_multisampled_target(
+                width, height, samples, factory)?;
+
+            let pso = factory.create_pipeline_from_program(
+                resolve_shader,
+                gfx::Primitive::TriangleList,
+                gfx::state::Rasterizer::new_fill(),
+                resolve_pipe::new())
+                .map_err(|e| gfx::PipelineStateError::<String>::from(e))?;
+            let data = resolve_pipe::Data {
+                vertex_buffer: vertex_buffer.clone(),
+                scene: ms_view,
+                samples: samples as i32,
+                out: resolved_target,
+            };
+            let bundle = gfx::pso::bundle::Bundle::new(
+                slice.clone(), pso, data);
+            (ms_target, Some(bundle))
+        }
+        else {
+            (resolved_target, None)
+        };
+
+        let pso = factory.create_pipeline_from_program(
+            shader,
+            gfx::Primitive::TriangleList,
+            gfx::state::Rasterizer::new_fill(),
+            pipe::new())
+            .map_err(|e| gfx::PipelineStateError::<String>::from(e))?;
+        let data = pipe::Data {
+            vertex_buffer,
+            scene: (scene_view, sampler),
+            chaos: 0,
+            confuse: 0,
+            shake: 0,
+            time: 0.0,
+            out: fb,
+        };
+        let pso_bundle = gfx::pso::bundle::Bundle::new(slice, pso, data);
+
+        Ok(Self {
+            scene_target,
+            resolve_bundle,
+            pso_bundle,
+        })
+    }
+
+    // Everything that should be post processed must be drawn to this target.
+    pub fn scene_target(&self) -> &RenderTargetView<R> {
+        &self.scene_target
+    }
+
+    pub fn begin_render<C: gfx::CommandBuffer<R>>(
+        &mut self, encoder: &mut gfx::Encoder<R, C>)
+    {
+        encoder.clear(&self.scene_target, [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    // Call once the scene has been drawn.
+    pub fn end_render<C: gfx::CommandBuffer<R>>(
+        &mut self, encoder: &mut gfx::Encoder<R, C>)
+    {
+        if let Some(ref bundle) = self.resolve_bundle {
+            bundle.encode(encoder);
+        }
+    }
+
+    // Draw the scene onto the framebuffer. Time is in seconds, and drives
+    // the shake and chaos animations.
+    pub fn render<C: gfx::CommandBuffer<R>>(
+        &mut self, effects: ScreenEffects, time: f32,
+        encoder: &mut gfx::Encoder<R, C>)
+    {
+        {
+            let data = &mut self.pso_bundle.data;
+            data.chaos = effects.chaos as i32;
+            data.confuse = effects.confuse as i32;
+            data.shake = effects.shake as i32;
+            data.time = time;
+        }
+        self.pso_bundle.encode(encoder);
+    }
+
+    fn create_multisampled_target<F: gfx::traits::FactoryExt<R>>(
+        width: u16, height: u16, samples: u8, factory: &mut F)
+        -> Result<(RenderTargetView<R>,
+                   gfx::handle::ShaderResourceView<R, [f32; 4]>)>
+    {
+        use self::gfx::format::{ChannelTyped, Formatted};
+        use self::gfx::memory::{Bind, Usage};
+        use self::gfx::texture::{AaMode, Kind};
+        type Channel = <ColourFormat as Formatted>::Channel;
+
+        let kind = Kind::D2(width, height, AaMode::Multi(samples));
+        let texture = factory.create_texture::<<ColourFormat as Formatted>::Surface>(
+            kind, 1, Bind::SHADER_RESOURCE | Bind::RENDER_TARGET, Usage::Data,
+            Some(Channel::get_channel_type()))
+            .map_err(gfx::CombinedError::from)?;
+        let view = factory.view_texture_as_shader_resource::<ColourFormat>(
+            &texture, (0, 0), gfx::format::Swizzle::new())
+            .map_err(gfx::CombinedError::from)?;
+        let target = factory.view_texture_as_render_target(&texture, 0, None)
+            .map_err(gfx::CombinedError::from)?;
+        Ok((target, view))
+    }
+}
+
+
+gfx_defines! {
+    vertex Vertex {
+        pos: [f32; 2] = "position",
+        uv: [f32; 2] = "texCoords",
+    }
+
+    pipeline pipe {
+        vertex_buffer: gfx::VertexBuffer<Vertex> = (),
+        scene: gfx::TextureSampler<[f32; 4]> = "scene",
+        // GLSL bools can't be set through gfx, so these are ints.
+        chaos: gfx::Global<i32> = "chaos",
+        confuse: gfx::Global<i32> = "confuse",
+        shake: gfx::Global<i32> = "shake",
+        time: gfx::Global<f32> = "time",
+        out: gfx::RenderTarget<ColourFormat> = "target",
+    }
+
+    // Multisampled textures can't be filtered, so there's no sampler here.
+    pipeline resolve_pipe {
+        vertex_buffer: gfx::VertexBuffer<Vertex> = (),
+        scene: gfx::ShaderResource<[f32; 4]> = "scene",
+        samples: gfx::Global<i32> = "samples",
+        out: gfx::RenderTarget<ColourFormat> = "target",
+    }
+}
@@ -0,0 +1,189 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// The original code used a string to identify the type of powerup. An enum
+// is a much better fit, and lets the compiler check that we've handled every
+// kind.
+
+use std::rc::Rc;
+
+use cgmath;
+
+use animation::{AnimationClip, PlayMode};
+use game_object::GameObject;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerUpKind {
+    Speed,
+    Sticky,
+    PassThrough,
+    PadSizeIncrease,
+    Confuse,
+    Chaos,
+    // Splits the ball into three.
+    Multiball,
+}
+
+impl PowerUpKind {
+    pub fn all() -> &'static [PowerUpKind] {
+        use self::PowerUpKind::*;
+        const ALL: &[PowerUpKind] = &[
+            Speed, Sticky, PassThrough, PadSizeIncrease, Confuse, Chaos,
+            Multiball];
+        ALL
+    }
+
+    // How long the effect lasts once it's been collected, in seconds.
+    // Zero means the effect is permanent (until the player loses the ball).
+    pub fn duration(&self) -> f32 {
+        use self::PowerUpKind::*;
+
+        match *self {
+            Speed => 0.0,
+            Sticky => 20.0,
+            PassThrough => 10.0,
+            PadSizeIncrease => 0.0,
+            Confuse => 15.0,
+            Chaos => 15.0,
+            Multiball => 0.0,
+        }
+    }
+
+    // There is a 1 in spawn_chance() chance of this kind of powerup being
+    // spawned when a brick is destroyed. The negative powerups are more
+    // common, to keep things interesting.
+    pub fn spawn_chance(&self) -> u32 {
+        use self::PowerUpKind::*;
+
+        match *self {
+            Confuse | Chaos => 15,
+            _ => 75,
+        }
+    }
+
+    fn colour(&self) -> cgmath::Vector3<f32> {
+        use self::PowerUpKind::*;
+        use self::cgmath::vec3;
+
+        match *self {
+            Speed => vec3(0.5, 0.5, 1.0),
+            Sticky => vec3(1.0, 0.5, 1.0),
+            PassThrough => vec3(0.5, 1.0, 0.5),
+            PadSizeIncrease => vec3(1.0, 0.6, 0.4),
+            Confuse => vec3(1.0, 0.3, 0.3),
+            Chaos => vec3(0.9, 0.25, 0.25),
+            Multiball => vec3(0.4, 0.9, 1.0),
+        }
+    }
+}
+
+
+// A powerup falls from the brick that spawned it. If the player catches it
+// with the paddle it is activated, and its effect lasts until the duration
+// runs out.
+#[derive(Clone)]
+pub struct PowerUp {
+    obj: GameObject,
+    kind: PowerUpKind,
+    remaining: f32,
+    activated: bool,
+}
+
+impl PowerUp {
+    pub fn size() -> cgmath::Vector2<f32> {
+        cgmath::vec2(60.0, 20.0)
+    }
+
+    pub fn velocity() -> cgmath::Vector2<f32> {
+        cgmath::vec2(0.0, 150.0)
+    }
+
+    pub fn new(kind: PowerUpKind, position: cgmath::Vector2<f32>) -> Self {
+        // We don't have a texture for each kind of powerup, so they're all
+        // drawn with the shimmer animation, tinted with the kind's colour.
+        // The colour is enough to tell the kinds apart. Playing the clip
+        // replaces the block sprite straight away.
+        let mut obj = GameObject::new(
+            position, Self::size(), "block", kind.colour());
+        obj.velocity = Self::velocity();
+        obj.play(Rc::new(shimmer()));
+        Self {
+            obj,
+            kind,
+            remaining: kind.duration(),
+            activated: false,
+        }
+    }
+
+    // Every sprite a powerup could be drawn with.
+    pub fn sprite_names() -> Vec<String> {
+        shimmer().sprite_names()
+    }
+
+    pub fn kind(&self) -> PowerUpKind {
+        self.kind
+    }
+
+    pub fn object(&self) -> &GameObject {
+        &self.obj
+    }
+
+    // A powerup stays active until its time runs out, even after it has been
+    // collected and is no longer drawn.
+    pub fn is_activated(&self) -> bool {
+        self.activated
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.obj.is_destroyed
+    }
+
+    // Finished with -- it's either been missed, or its effect has expired.
+    pub fn is_spent(&self) -> bool {
+        self.obj.is_destroyed && !self.activated
+    }
+
+    pub fn activate(&mut self) {
+        self.activated = true;
+        self.obj.is_destroyed = true;
+    }
+
+    pub fn store_position(&mut self) {
+        self.obj.store_position();
+    }
+
+    // Move the powerup and count down its duration. Returns true if the
+    // effect expired during this update.
+    pub fn update(&mut self, delta_time: f32, window_height: f32) -> bool {
+        self.obj.position += self.obj.velocity * delta_time;
+        self.obj.animate(delta_time);
+        if self.obj.position.y >= window_height {
+            self.obj.is_destroyed = true;
+        }
+
+        if self.activated {
+            self.remaining -= delta_time;
+            if self.remaining <= 0.0 {
+                self.activated = false;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn shimmer() -> AnimationClip {
+    AnimationClip::from_sheet("powerup_shimmer", 4, 0.1, PlayMode::PingPong)
+}
@@ -28,11 +28,30 @@ pub type ColourFormat = gfx::format::Rgba8;
 pub type RenderTargetView<R: gfx::Resources> =
     gfx::handle::RenderTargetView<R, ColourFormat>;
 
+// The UV rect that covers a whole texture.
+pub const FULL_TEXTURE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+// How many sprites we can queue up before we are forced to flush them. This
+// is also the size of the instance buffer, so a single draw call never
+// covers more sprites than this.
+const MAX_INSTANCES: usize = 1024;
+
 pub struct SpriteRenderer <R: gfx::Resources> {
     // We can use the Bundle struct to slightly simplify the storage of the PSO
     // and associated data, since the set of vertices we draw never changes
-    // between frames.
+    // between frames. Only the instance count in the slice changes.
     pso_bundle: gfx::pso::bundle::Bundle<R, pipe::Data<R>>,
+    // Sprites that have been queued but not yet drawn. They are grouped by
+    // texture, in the order that each texture was first used.
+    batches: Vec<Batch<R>>,
+    queued: usize,
+}
+
+// All the queued sprites that share a texture. These go out in one
+// instanced draw call.
+struct Batch <R: gfx::Resources> {
+    texture: gfx::handle::ShaderResourceView<R, [f32; 4]>,
+    instances: Vec<Instance>,
 }
 
 impl <R: gfx::Resources> SpriteRenderer <R> {
@@ -67,6 +86,14 @@ impl <R: gfx::Resources> SpriteRenderer <R> {
         let (vertex_buffer, slice) =
             factory.create_vertex_buffer_with_slice(VERTICES, INDICES);
 
+        // The per-sprite data lives here. It's rewritten for every batch, so
+        // it needs to be dynamic.
+        let instances = factory.create_buffer(
+            MAX_INSTANCES,
+            gfx::buffer::Role::Vertex,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty())?;
+
         // The sampler must be created in a valid state, so we need to give it
         // some sort of texture now.
         // I was lazy and created a dummy 1x1 texture here. Once we render a
@@ -85,8 +112,8 @@ impl <R: gfx::Resources> SpriteRenderer <R> {
 
         let data = pipe::Data {
             vertex_buffer,
+            instances,
             sprite_sampler: (default_texture.view, sampler),
-            locals: factory.create_constant_buffer(1),
             projection: projection.clone().into(),
             out: fb,
         };
@@ -96,6 +123,8 @@ impl <R: gfx::Resources> SpriteRenderer <R> {
 
         Ok(Self {
             pso_bundle,
+            batches: Vec::new(),
+            queued: 0,
         })
     }
 
@@ -107,9 +136,36 @@ impl <R: gfx::Resources> SpriteRenderer <R> {
         rotation: f32,
         colour: cgmath::Vector3<f32>,
         encoder: &mut gfx::Encoder<R, C>)
+    {
+        self.draw_sprite_region(
+            texture, FULL_TEXTURE, position, size, rotation, colour, encoder);
+    }
+
+    // Draw only part of the texture. The UV rect is [left, top, width,
+    // height], with (0,0) being the top-left of the texture and (1,1) the
+    // bottom-right.
+    //
+    // Nothing actually gets drawn here -- the sprite is queued up, and goes
+    // out with all the other sprites that use the same texture when `flush`
+    // is called. That means sprites with different textures don't
+    // necessarily come out in the order they were queued, so call `flush`
+    // between anything that has to be layered on top of something else.
+    pub fn draw_sprite_region<C: gfx::CommandBuffer<R>>(
+        &mut self,
+        texture: &texture::Texture2D<R>,
+        uv_rect: [f32; 4],
+        position: cgmath::Vector2<f32>,
+        size: cgmath::Vector2<f32>,
+        rotation: f32,
+        colour: cgmath::Vector3<f32>,
+        encoder: &mut gfx::Encoder<R, C>)
     {
         use self::cgmath::{Deg, Matrix4};
 
+        if self.queued >= MAX_INSTANCES {
+            self.flush(encoder);
+        }
+
         // Making the individual matrices like this makes it easy to follow
         // what is going on, but is probably not ideal for memory.
         // Or is rustc smart enough to optimise away some of this?
@@ -130,16 +186,46 @@ impl <R: gfx::Resources> SpriteRenderer <R> {
         let scale = Matrix4::from_nonuniform_scale(size.x, size.y, 1.0);
 
         // Combine -- scale then rotate then position.
-        let model = trans * rot * scale;
+        let model: [[f32; 4]; 4] = (trans * rot * scale).into();
 
-        let locals = Locals {
+        let instance = Instance {
+            model_col0: model[0],
+            model_col1: model[1],
+            model_col2: model[2],
+            model_col3: model[3],
             colour: colour.extend(1.0).into(),
-            model: model.into(),
+            uv_rect,
         };
 
-        encoder.update_constant_buffer(&self.pso_bundle.data.locals, &locals);
-        self.pso_bundle.data.sprite_sampler.0 = texture.view.clone();
-        self.pso_bundle.encode(encoder);
+        // Texture views compare equal if they refer to the same underlying
+        // texture, which is exactly what we need to group by.
+        match self.batches.iter().position(|b| b.texture == texture.view) {
+            Some(i) => self.batches[i].instances.push(instance),
+            None => self.batches.push(Batch {
+                texture: texture.view.clone(),
+                instances: vec![instance],
+            }),
+        }
+        self.queued += 1;
+    }
+
+    // Draw everything that has been queued, one instanced draw call per
+    // texture.
+    pub fn flush<C: gfx::CommandBuffer<R>>(
+        &mut self, encoder: &mut gfx::Encoder<R, C>)
+    {
+        for batch in self.batches.drain(..) {
+            // The instance buffer is the same size as the queue limit, so a
+            // batch always fits.
+            encoder.update_buffer(
+                &self.pso_bundle.data.instances, &batch.instances, 0)
+                .expect("sprite batch is larger than the instance buffer");
+            self.pso_bundle.slice.instances =
+                Some((batch.instances.len() as gfx::InstanceCount, 0));
+            self.pso_bundle.data.sprite_sampler.0 = batch.texture;
+            self.pso_bundle.encode(encoder);
+        }
+        self.queued = 0;
     }
 }
 
@@ -150,20 +236,25 @@ gfx_defines! {
         uv: [f32; 2] = "texCoords",
     }
 
-    // Some deviation from the original code, just for the sake of
-    // experimenting. The colour and model are now stored in a UBO (assuming
-    // OpenGL backend).
-    // Could we compute the colour and model once per game object and store
-    // the data, instead of re-computing it for every sprite on every frame?
-    constant Locals {
+    // Everything that differs from one sprite to the next. This used to live
+    // in a constant buffer that was rewritten before every draw call; now
+    // there is one of these per sprite in an instance buffer, so a whole
+    // batch can go out in one draw call.
+    // The model matrix is split into its columns since vertex attributes
+    // can't be bigger than a vec4.
+    vertex Instance {
+        model_col0: [f32; 4] = "modelCol0",
+        model_col1: [f32; 4] = "modelCol1",
+        model_col2: [f32; 4] = "modelCol2",
+        model_col3: [f32; 4] = "modelCol3",
         colour: [f32; 4] = "spriteColour",
-        model: [[f32; 4]; 4] = "model",
+        uv_rect: [f32; 4] = "uvRect",
     }
 
     pipeline pipe {
         vertex_buffer: gfx::VertexBuffer<Vertex> = (),
+        instances: gfx::InstanceBuffer<Instance> = (),
         sprite_sampler: gfx::TextureSampler<[f32; 4]> = "image",
-        locals: gfx::ConstantBuffer<Locals> = "Locals",
         projection: gfx::Global<[[f32; 4]; 4]> = "projection",
         // Use BlendTarget for any transparency that is more complicated than
         // on/off.
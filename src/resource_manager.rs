@@ -30,14 +30,29 @@ use std::path::Path;
 use gfx;
 use image;
 
+use animation;
+use atlas;
+use audio::AudioManager;
 use errors::*;
+use text_renderer;
 use texture;
 
 
+// Big enough to hold all of the game's sprites on one page.
+const ATLAS_PAGE_SIZE: u32 = 1024;
+const ATLAS_PADDING: u32 = 2;
+
 pub struct ResourceManager<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> {
     factory: F,
     shaders: HashMap<String, gfx::handle::Program<R>>,
     textures: HashMap<String, texture::Texture2D<R>>,
+    // Every texture is also available as a sprite, along with everything
+    // that has been packed into an atlas.
+    sprites: HashMap<String, texture::Sprite<R>>,
+    // Images waiting to be packed by build_atlases.
+    atlas_builder: atlas::AtlasBuilder,
+    fonts: HashMap<String, text_renderer::Font<R>>,
+    audio: AudioManager,
 }
 
 impl<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> ResourceManager<F, R> {
@@ -46,6 +61,11 @@ impl<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> ResourceManager<F, R> {
             factory,
             shaders: HashMap::with_capacity(10),
             textures: HashMap::with_capacity(10),
+            sprites: HashMap::with_capacity(10),
+            atlas_builder: atlas::AtlasBuilder::new(
+                ATLAS_PAGE_SIZE, ATLAS_PADDING),
+            fonts: HashMap::with_capacity(2),
+            audio: AudioManager::null(),
         }
     }
 
@@ -75,6 +95,8 @@ impl<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> ResourceManager<F, R> {
         -> Result<()>
     {
         let texture = self.load_texture_from_file(path.as_ref())?;
+        self.sprites.insert(
+            name.clone(), texture::Sprite::whole(texture.clone()));
         self.textures.insert(name, texture);
         Ok(())
     }
@@ -83,6 +105,96 @@ impl<F: gfx::traits::FactoryExt<R>, R: gfx::Resources> ResourceManager<F, R> {
         self.textures.get(name)
     }
 
+    // Queue up an image to be packed into an atlas. It won't be available
+    // as a sprite until build_atlases is called, and it never becomes a
+    // texture in its own right.
+    pub fn load_atlas_image<P: AsRef<Path>>(
+        &mut self, path: &P, name: String)
+        -> Result<()>
+    {
+        let img = load_image(path.as_ref())?;
+        self.atlas_builder.add(name, img);
+        Ok(())
+    }
+
+    // Split a sprite sheet into frames of the given size, reading left to
+    // right then top to bottom, and queue them up to be packed into an atlas.
+    // The frames become sprites named "<name>_0", "<name>_1", and so on --
+    // see animation::sheet_frame_name.
+    pub fn load_sprite_sheet<P: AsRef<Path>>(
+        &mut self, path: &P, frame_width: u32, frame_height: u32,
+        name: String)
+        -> Result<()>
+    {
+        let img = load_image(path.as_ref())?;
+        let frames = atlas::split_sheet(&img, frame_width, frame_height)?;
+        for (i, frame) in frames.into_iter().enumerate() {
+            self.atlas_builder.add(animation::sheet_frame_name(&name, i), frame);
+        }
+        Ok(())
+    }
+
+    // Pack everything queued by load_atlas_image into as few textures as
+    // possible. This can be called more than once, but images packed by
+    // different calls will never share a texture.
+    pub fn build_atlases(&mut self) -> Result<()> {
+        if self.atlas_builder.is_empty() {
+            return Ok(());
+        }
+        for page in self.atlas_builder.build()? {
+            let uv_rects: Vec<_> = page.regions.iter()
+                .map(|&(ref name, ref rect)| (name.clone(), page.uv_rect(rect)))
+                .collect();
+            let texture = texture::Texture2D::new(
+                page.image, &mut self.factory)?;
+            for (name, uv_rect) in uv_rects {
+                let sprite = texture::Sprite {
+                    texture: texture.clone(),
+                    uv_rect,
+                };
+                self.sprites.insert(name, sprite);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<&texture::Sprite<R>> {
+        self.sprites.get(name)
+    }
+
+    // Load a TrueType font, rendered at a fixed size. Text can be scaled
+    // when it's drawn, but it looks best close to the size it was loaded at.
+    pub fn load_font<P: AsRef<Path>>(
+        &mut self, path: &P, pixel_height: f32, name: String)
+        -> Result<()>
+    {
+        let data = read_code(path)?;
+        let font = text_renderer::Font::from_bytes(
+            data, pixel_height, &mut self.factory)?;
+        self.fonts.insert(name, font);
+        Ok(())
+    }
+
+    pub fn font(&self, name: &str) -> Option<&text_renderer::Font<R>> {
+        self.fonts.get(name)
+    }
+
+    // Sounds are loaded into whichever audio manager we've been given. The
+    // same manager should be handed to the game, so that it can play them.
+    pub fn set_audio(&mut self, audio: AudioManager) {
+        self.audio = audio;
+    }
+
+    pub fn audio(&self) -> &AudioManager {
+        &self.audio
+    }
+
+    pub fn load_sound<P: AsRef<Path>>(&mut self, path: &P, name: String)
+        -> Result<()>
+    {
+        self.audio.load_sound(path, name)
+    }
+
     fn load_shader_from_file<P: AsRef<Path>>(
         &mut self,
         v_shader_path: &P, p_shader_path: &P, g_shader_path: Option<&P>)
@@ -0,0 +1,172 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code, which had no score at all.
+//
+// Each brick is worth some points when it's destroyed. Hitting bricks one
+// after the other without the ball touching the paddle builds up a combo,
+// which multiplies the points. Clearing a level is worth a bonus, plus a
+// little more for every life that's left.
+
+
+pub const LEVEL_CLEAR_BONUS: u32 = 1000;
+pub const LIFE_BONUS: u32 = 250;
+// The multiplier goes up by one for every this many hits in a combo...
+const HITS_PER_MULTIPLIER: u32 = 4;
+// ...but never goes higher than this.
+const MAX_MULTIPLIER: u32 = 8;
+
+#[derive(Clone, Debug, Default)]
+pub struct Score {
+    points: u32,
+    // Bricks hit since the ball last touched the paddle.
+    combo: u32,
+    best_combo: u32,
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn points(&self) -> u32 {
+        self.points
+    }
+
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    // The longest combo so far this game.
+    pub fn best_combo(&self) -> u32 {
+        self.best_combo
+    }
+
+    // What the next brick's points will be multiplied by.
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / HITS_PER_MULTIPLIER).min(MAX_MULTIPLIER)
+    }
+
+    // Any hit on a breakable brick keeps the combo going, but only
+    // destroying it scores points. Returns the points awarded.
+    pub fn brick_hit(&mut self, points: u32) -> u32 {
+        // Levels can make bricks worth a lot, but the score just stops at
+        // the top rather than overflowing.
+        let awarded = points.saturating_mul(self.multiplier());
+        self.points = self.points.saturating_add(awarded);
+        self.combo = self.combo.saturating_add(1);
+        self.best_combo = self.best_combo.max(self.combo);
+        awarded
+    }
+
+    // Touching the paddle (or losing the ball) ends the combo.
+    pub fn end_combo(&mut self) {
+        self.combo = 0;
+    }
+
+    // Returns the points awarded.
+    pub fn level_cleared(&mut self, lives_left: u32) -> u32 {
+        let awarded = LIFE_BONUS.saturating_mul(lives_left)
+            .saturating_add(LEVEL_CLEAR_BONUS);
+        self.points = self.points.saturating_add(awarded);
+        self.end_combo();
+        awarded
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combo_builds_the_multiplier() {
+        let mut score = Score::new();
+        let awarded: Vec<u32> = (0..9).map(|_| score.brick_hit(10)).collect();
+        assert_eq!(awarded, [10, 10, 10, 10, 20, 20, 20, 20, 30]);
+        assert_eq!(score.points(), 150);
+        assert_eq!(score.combo(), 9);
+        assert_eq!(score.multiplier(), 3);
+    }
+
+    #[test]
+    fn multiplier_has_a_limit() {
+        let mut score = Score::new();
+        for _ in 0..(HITS_PER_MULTIPLIER * MAX_MULTIPLIER * 2) {
+            score.brick_hit(0);
+        }
+        assert_eq!(score.multiplier(), MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn damage_keeps_the_combo_going() {
+        // Bricks that are only damaged score nothing, but still count.
+        let mut score = Score::new();
+        for _ in 0..HITS_PER_MULTIPLIER {
+            assert_eq!(score.brick_hit(0), 0);
+        }
+        assert_eq!(score.brick_hit(10), 20);
+    }
+
+    #[test]
+    fn ending_the_combo() {
+        let mut score = Score::new();
+        for _ in 0..6 {
+            score.brick_hit(10);
+        }
+        score.end_combo();
+        assert_eq!(score.combo(), 0);
+        assert_eq!(score.multiplier(), 1);
+        assert_eq!(score.brick_hit(10), 10);
+        // The best combo is still remembered.
+        assert_eq!(score.best_combo(), 6);
+    }
+
+    #[test]
+    fn clearing_a_level_is_worth_more_with_lives_left() {
+        let mut score = Score::new();
+        score.brick_hit(10);
+        assert_eq!(score.level_cleared(3), LEVEL_CLEAR_BONUS + 3 * LIFE_BONUS);
+        assert_eq!(score.points(), 10 + LEVEL_CLEAR_BONUS + 3 * LIFE_BONUS);
+        assert_eq!(score.combo(), 0);
+
+        let mut score = Score::new();
+        assert_eq!(score.level_cleared(0), LEVEL_CLEAR_BONUS);
+    }
+
+    #[test]
+    fn huge_scores_stop_at_the_top() {
+        let mut score = Score::new();
+        for _ in 0..HITS_PER_MULTIPLIER {
+            score.brick_hit(0);
+        }
+        assert_eq!(score.brick_hit(u32::max_value()), u32::max_value());
+        score.brick_hit(u32::max_value());
+        score.level_cleared(u32::max_value());
+        assert_eq!(score.points(), u32::max_value());
+    }
+
+    #[test]
+    fn reset() {
+        let mut score = Score::new();
+        score.brick_hit(10);
+        score.reset();
+        assert_eq!(score.points(), 0);
+        assert_eq!(score.best_combo(), 0);
+    }
+}
@@ -0,0 +1,221 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code, which checked the ball against every brick
+// in the level, every frame. That's fine for the 150 or so bricks in the
+// built-in levels, but a big custom level can have thousands.
+//
+// A uniform grid is laid over the level, and each cell remembers which boxes
+// overlap it. Finding what's near the ball only means looking in the few
+// cells it could reach. The grid only stores indices, so it's up to the
+// owner to keep it in step with whatever the indices refer to. For bricks
+// that's easy, since they never move.
+
+use cgmath;
+
+
+pub struct SpatialGrid {
+    // The top-left corner of the top-left cell.
+    origin: cgmath::Vector2<f32>,
+    cell_size: cgmath::Vector2<f32>,
+    columns: usize,
+    rows: usize,
+    // Row by row, starting from the top-left.
+    cells: Vec<Vec<usize>>,
+}
+
+impl SpatialGrid {
+    // A grid with enough cells to cover an area of the given size. Boxes
+    // outside the area are still found, they just go in the nearest cell at
+    // the edge.
+    pub fn new(
+        origin: cgmath::Vector2<f32>, size: cgmath::Vector2<f32>,
+        cell_size: cgmath::Vector2<f32>)
+        -> Self
+    {
+        assert!(cell_size.x > 0.0 && cell_size.y > 0.0,
+                "grid cells must have a positive size");
+        let columns = ((size.x / cell_size.x).ceil() as usize).max(1);
+        let rows = ((size.y / cell_size.y).ceil() as usize).max(1);
+        Self {
+            origin,
+            cell_size,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+        }
+    }
+
+    // File index under every cell that the box (top-left corner and size)
+    // overlaps.
+    pub fn insert(
+        &mut self, index: usize,
+        position: cgmath::Vector2<f32>, size: cgmath::Vector2<f32>)
+    {
+        let first = self.cell_at(position);
+        let last = self.cell_at(position + size);
+        for row in first.1..last.1 + 1 {
+            for column in first.0..last.0 + 1 {
+                self.cells[row * self.columns + column].push(index);
+            }
+        }
+    }
+
+    // Fill found with the index of everything that might overlap the area
+    // between min and max (top-left and bottom-right). Anything that does
+    // overlap it is always found, but so may a few things that don't. The
+    // indices are in ascending order with no repeats, so they come out in
+    // the same order as a scan through everything would find them.
+    pub fn query(
+        &self, min: cgmath::Vector2<f32>, max: cgmath::Vector2<f32>,
+        found: &mut Vec<usize>)
+    {
+        found.clear();
+        let first = self.cell_at(min);
+        let last = self.cell_at(max);
+        for row in first.1..last.1 + 1 {
+            for column in first.0..last.0 + 1 {
+                let cell = &self.cells[row * self.columns + column];
+                found.extend_from_slice(cell);
+            }
+        }
+        found.sort();
+        found.dedup();
+    }
+
+    // The (column, row) of the cell a point is in, clamped to the grid.
+    fn cell_at(&self, point: cgmath::Vector2<f32>) -> (usize, usize) {
+        let offset = point - self.origin;
+        let column = (offset.x / self.cell_size.x).floor();
+        let row = (offset.y / self.cell_size.y).floor();
+        // Negative (and NaN) values become 0 when cast.
+        (
+            (column.max(0.0) as usize).min(self.columns - 1),
+            (row.max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::vec2;
+    use rand;
+    use rand::{Rng, SeedableRng};
+
+    type Vec2 = cgmath::Vector2<f32>;
+
+    // 10 by 10 cells, each 10 by 10.
+    fn grid() -> SpatialGrid {
+        SpatialGrid::new(vec2(0.0, 0.0), vec2(100.0, 100.0), vec2(10.0, 10.0))
+    }
+
+    fn query(grid: &SpatialGrid, min: Vec2, max: Vec2) -> Vec<usize> {
+        let mut found = Vec::new();
+        grid.query(min, max, &mut found);
+        found
+    }
+
+    // What a scan through every box would find. Touching counts.
+    fn scan(boxes: &[(Vec2, Vec2)], min: Vec2, max: Vec2) -> Vec<usize> {
+        boxes.iter().enumerate()
+            .filter(|&(_, &(position, size))| {
+                position.x <= max.x && min.x <= position.x + size.x
+                    && position.y <= max.y && min.y <= position.y + size.y
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn finds_everything_a_scan_does() {
+        let mut rng = rand::XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut grid = grid();
+        // Including some that hang off the edges.
+        let boxes: Vec<_> = (0..200)
+            .map(|_| {
+                let position = vec2(
+                    rng.gen_range(-20.0, 110.0), rng.gen_range(-20.0, 110.0));
+                let size = vec2(
+                    rng.gen_range(1.0, 25.0), rng.gen_range(1.0, 25.0));
+                (position, size)
+            })
+            .collect();
+        for (i, &(position, size)) in boxes.iter().enumerate() {
+            grid.insert(i, position, size);
+        }
+
+        let mut found = Vec::new();
+        for _ in 0..500 {
+            let min = vec2(
+                rng.gen_range(-50.0, 150.0), rng.gen_range(-50.0, 150.0));
+            let max = min + vec2(
+                rng.gen_range(0.0, 40.0), rng.gen_range(0.0, 40.0));
+            grid.query(min, max, &mut found);
+
+            let expected = scan(&boxes, min, max);
+            assert!(expected.iter().all(|i| found.contains(i)),
+                    "{:?} to {:?}: {:?} missing from {:?}",
+                    min, max, expected, found);
+            // In order, with no repeats.
+            assert!(found.windows(2).all(|w| w[0] < w[1]), "{:?}", found);
+        }
+    }
+
+    #[test]
+    fn cell_boundaries() {
+        let mut grid = grid();
+        // Exactly filling the cell at (1, 1), so it ends on the boundary
+        // with the cells after it.
+        grid.insert(0, vec2(10.0, 10.0), vec2(10.0, 10.0));
+
+        // A point on its far edge, in the next cell.
+        assert_eq!(query(&grid, vec2(20.0, 15.0), vec2(20.0, 15.0)), vec![0]);
+        assert_eq!(query(&grid, vec2(15.0, 20.0), vec2(15.0, 20.0)), vec![0]);
+        // A point on its near edge.
+        assert_eq!(query(&grid, vec2(10.0, 10.0), vec2(10.0, 10.0)), vec![0]);
+        // Ending just before it.
+        assert!(query(&grid, vec2(0.0, 0.0), vec2(9.99, 9.99)).is_empty());
+    }
+
+    #[test]
+    fn outside_the_grid() {
+        let mut grid = grid();
+        // Off the left of the grid, and in the bottom-right corner.
+        grid.insert(0, vec2(-30.0, 40.0), vec2(10.0, 10.0));
+        grid.insert(1, vec2(95.0, 95.0), vec2(10.0, 10.0));
+
+        assert_eq!(query(&grid, vec2(-25.0, 45.0), vec2(-25.0, 45.0)),
+                   vec![0]);
+        assert_eq!(query(&grid, vec2(500.0, 500.0), vec2(600.0, 600.0)),
+                   vec![1]);
+        // Everything, and then some.
+        assert_eq!(query(&grid, vec2(-1e6, -1e6), vec2(1e6, 1e6)),
+                   vec![0, 1]);
+        // NaN ends up in the first cell, rather than panicking.
+        let nan = ::std::f32::NAN;
+        assert!(query(&grid, vec2(nan, nan), vec2(nan, nan)).is_empty());
+    }
+
+    #[test]
+    fn query_clears_what_was_found_before() {
+        let mut grid = grid();
+        grid.insert(0, vec2(0.0, 0.0), vec2(5.0, 5.0));
+        let mut found = vec![7, 8, 9];
+        grid.query(vec2(0.0, 0.0), vec2(1.0, 1.0), &mut found);
+        assert_eq!(found, vec![0]);
+    }
+}
@@ -0,0 +1,203 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// The original code used FreeType to make a separate texture for every
+// character, and had its own shader for text. We use rusttype (a pure Rust
+// alternative to FreeType) instead, and draw all of the characters into one
+// texture -- a glyph atlas. Each character is then just a sprite that uses a
+// small part of the atlas, so the normal sprite renderer can draw it.
+
+use std::collections::HashMap;
+
+use cgmath;
+use gfx;
+use image;
+use rusttype;
+
+use errors::*;
+use renderer;
+use texture;
+
+
+// Only printable ASCII is supported, which is plenty for this game.
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+const ATLAS_COLUMNS: u32 = 16;
+
+// Where a character is in the atlas, and how to position it. All of the
+// sizes are in pixels, at a scale of 1.0.
+#[derive(Clone)]
+pub struct Glyph {
+    pub uv_rect: [f32; 4],
+    pub size: cgmath::Vector2<f32>,
+    // Offset from the top-left of the line to the top-left of the glyph.
+    pub bearing: cgmath::Vector2<f32>,
+    // How far to move along before drawing the next character.
+    pub advance: f32,
+}
+
+#[derive(Clone)]
+pub struct Font<R: gfx::Resources> {
+    texture: texture::Texture2D<R>,
+    glyphs: HashMap<char, Glyph>,
+    line_height: f32,
+}
+
+impl<R: gfx::Resources> Font<R> {
+    // Rasterise the font at the given height, and upload the atlas.
+    pub fn from_bytes<F: gfx::traits::FactoryExt<R>>(
+        data: Vec<u8>, pixel_height: f32, factory: &mut F)
+        -> Result<Self>
+    {
+        let font = rusttype::Font::from_bytes(data)?;
+        let scale = rusttype::Scale::uniform(pixel_height);
+        let v_metrics = font.v_metrics(scale);
+        let line_height =
+            v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+        // Position every glyph with the top of the line at y = 0, so the
+        // bounding boxes are already relative to the top of the line.
+        let origin = rusttype::point(0.0, v_metrics.ascent);
+        let positioned: Vec<_> = (FIRST_CHAR..LAST_CHAR + 1)
+            .map(|c| c as char)
+            .map(|c| (c, font.glyph(c).scaled(scale).positioned(origin)))
+            .collect();
+
+        // Every glyph gets the same size cell in the atlas, big enough for
+        // the largest. There's a pixel of padding around each one so that
+        // filtering doesn't pick up bits of the neighbours.
+        let (cell_width, cell_height) = positioned.iter()
+            .filter_map(|&(_, ref g)| g.pixel_bounding_box())
+            .fold((0, 0), |(w, h), bb| {
+                (w.max(bb.width() as u32), h.max(bb.height() as u32))
+            });
+        let (cell_width, cell_height) = (cell_width + 2, cell_height + 2);
+        let rows = (positioned.len() as u32 + ATLAS_COLUMNS - 1) / ATLAS_COLUMNS;
+        let atlas_width = ATLAS_COLUMNS * cell_width;
+        let atlas_height = rows * cell_height;
+
+        // White everywhere, with the coverage in the alpha channel. Drawing
+        // a glyph as a sprite then tints it with the sprite colour.
+        let mut img = image::RgbaImage::from_pixel(
+            atlas_width, atlas_height,
+            image::Rgba { data: [255, 255, 255, 0] });
+        let mut glyphs = HashMap::with_capacity(positioned.len());
+
+        for (i, &(c, ref glyph)) in positioned.iter().enumerate() {
+            let advance = glyph.unpositioned().h_metrics().advance_width;
+            let bb = match glyph.pixel_bounding_box() {
+                Some(bb) => bb,
+                // Nothing to draw, e.g. a space.
+                None => {
+                    glyphs.insert(c, Glyph {
+                        uv_rect: [0.0, 0.0, 0.0, 0.0],
+                        size: cgmath::vec2(0.0, 0.0),
+                        bearing: cgmath::vec2(0.0, 0.0),
+                        advance,
+                    });
+                    continue;
+                }
+            };
+
+            let cell_x = (i as u32 % ATLAS_COLUMNS) * cell_width + 1;
+            let cell_y = (i as u32 / ATLAS_COLUMNS) * cell_height + 1;
+            glyph.draw(|x, y, v| {
+                let alpha = (v * 255.0).round() as u8;
+                img.put_pixel(
+                    cell_x + x, cell_y + y,
+                    image::Rgba { data: [255, 255, 255, alpha] });
+            });
+
+            let (width, height) = (bb.width() as f32, bb.height() as f32);
+            glyphs.insert(c, Glyph {
+                uv_rect: [
+                    cell_x as f32 / atlas_width as f32,
+                    cell_y as f32 / atlas_height as f32,
+                    width / atlas_width as f32,
+                    height / atlas_height as f32],
+                size: cgmath::vec2(width, height),
+                bearing: cgmath::vec2(bb.min.x as f32, bb.min.y as f32),
+                advance,
+            });
+        }
+
+        Ok(Self {
+            texture: texture::Texture2D::new(img, factory)?,
+            glyphs,
+            line_height,
+        })
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    // How wide the text will be when drawn at this scale.
+    pub fn text_width(&self, text: &str, scale: f32) -> f32 {
+        text.chars()
+            .map(|c| self.glyph(c).advance * scale)
+            .sum()
+    }
+
+    // Characters we don't have are drawn as '?'.
+    fn glyph(&self, c: char) -> &Glyph {
+        self.glyphs.get(&c)
+            .unwrap_or_else(|| &self.glyphs[&'?'])
+    }
+}
+
+
+pub struct TextRenderer<R: gfx::Resources> {
+    font: Font<R>,
+}
+
+impl<R: gfx::Resources> TextRenderer<R> {
+    pub fn new(font: Font<R>) -> Self {
+        Self {
+            font,
+        }
+    }
+
+    pub fn font(&self) -> &Font<R> {
+        &self.font
+    }
+
+    // Position is the top-left corner of the text.
+    pub fn draw_text<C: gfx::CommandBuffer<R>>(
+        &self,
+        renderer: &mut renderer::SpriteRenderer<R>,
+        text: &str,
+        position: cgmath::Vector2<f32>,
+        scale: f32,
+        colour: cgmath::Vector3<f32>,
+        encoder: &mut gfx::Encoder<R, C>)
+    {
+        let mut x = position.x;
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            if glyph.size.x > 0.0 {
+                let glyph_pos = cgmath::vec2(x, position.y) + glyph.bearing * scale;
+                renderer.draw_sprite_region(
+                    &self.font.texture,
+                    glyph.uv_rect,
+                    glyph_pos,
+                    glyph.size * scale,
+                    0.0,
+                    colour,
+                    encoder);
+            }
+            x += glyph.advance * scale;
+        }
+    }
+}
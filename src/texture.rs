@@ -16,6 +16,7 @@ use gfx;
 use image;
 
 use errors::*;
+use renderer;
 
 
 pub type TextureFormat = gfx::format::Rgba8;
@@ -54,3 +55,26 @@ impl<R> Texture2D<R>
         })
     }
 }
+
+// A handle to an image that might only be part of a texture, e.g. one entry
+// in an atlas or one frame of a sprite sheet. The UV rect is [left, top,
+// width, height], the same as SpriteRenderer::draw_sprite_region takes.
+#[derive(Clone)]
+pub struct Sprite<R>
+    where R: gfx::Resources
+{
+    pub texture: Texture2D<R>,
+    pub uv_rect: [f32; 4],
+}
+
+impl<R> Sprite<R>
+    where R: gfx::Resources
+{
+    // A sprite that covers the whole of a texture.
+    pub fn whole(texture: Texture2D<R>) -> Self {
+        Self {
+            texture,
+            uv_rect: renderer::FULL_TEXTURE,
+        }
+    }
+}
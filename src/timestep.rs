@@ -0,0 +1,128 @@
+// This code is part of Breakout.
+//
+// Breakout is free software: you can redistribute it and/or modify it under
+// the terms of the CC BY 4.0 license as published by Creative Commons, either
+// version 4 of the License, or (at your option) any later version.
+//
+// https://creativecommons.org/licenses/by/4.0/legalcode
+//
+// The original code is copyright Joey de Vries
+// (https://twitter.com/JoeyDeVriez) and can be found at
+// https://learnopengl.com/In-Practice/2D-Game/Breakout
+//
+// The original code was modified by Adrian Chan in order to port it to Rust.
+
+// Not part of the original code, which updated the game once per frame by
+// however long the frame took. That means a slow frame moves the ball a long
+// way in one go (possibly straight through a brick), and the game plays
+// slightly differently on every machine.
+//
+// Instead, the time each frame takes goes into an accumulator, and the game
+// is updated in fixed-size steps until the accumulator is used up. Whatever
+// is left over is less than one step, and is used to blend between the last
+// two states when drawing. See https://gafferongames.com/post/fix_your_timestep/
+//
+// The game only ever sees the fixed step, so the same inputs always produce
+// exactly the same game.
+
+
+pub struct FixedTimestep {
+    // Seconds. Kept as f64 so that the accumulator doesn't drift over a long
+    // session.
+    step: f64,
+    max_steps: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    // If a frame takes so long that it would need more than max_steps steps
+    // to catch up, the extra time is thrown away and the game slows down
+    // instead. Otherwise a slow machine could fall further and further
+    // behind, taking longer each frame to catch up.
+    pub fn new(step: f32, max_steps: u32) -> Self {
+        assert!(step > 0.0, "the timestep must be positive");
+        assert!(max_steps > 0, "at least one step per frame is needed");
+        Self {
+            step: step as f64,
+            max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    // The delta time to give the game on every update.
+    pub fn step(&self) -> f32 {
+        self.step as f32
+    }
+
+    // Add a frame's worth of time, and return how many steps to run.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += frame_time.max(0.0);
+        let mut steps = (self.accumulator / self.step).floor() as u32;
+        if steps > self.max_steps {
+            steps = self.max_steps;
+            self.accumulator = steps as f64 * self.step;
+        }
+        self.accumulator -= steps as f64 * self.step;
+        steps
+    }
+
+    // How far we are between the last step and the next one, from 0 to 1.
+    // Used to interpolate positions when drawing.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step) as f32
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A quarter of a second is exact in binary, so none of these need to
+    // allow for rounding.
+    const STEP: f32 = 0.25;
+
+    #[test]
+    fn left_over_time_carries_over() {
+        let mut timestep = FixedTimestep::new(STEP, 10);
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        // The half step left over makes this one up to a whole step.
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        // Not enough for a step yet.
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn steps_are_capped() {
+        let mut timestep = FixedTimestep::new(STEP, 4);
+        assert_eq!(timestep.advance(100.0), 4);
+        // The time that couldn't be caught up on is gone for good.
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn negative_frame_times_are_ignored() {
+        let mut timestep = FixedTimestep::new(STEP, 4);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn alpha_stays_between_steps() {
+        let mut timestep = FixedTimestep::new(1.0 / 120.0, 10);
+        // Frame times that don't line up with the step at all, including
+        // some long enough to hit the cap.
+        let mut frame_time = 0.0013;
+        for _ in 0..10_000 {
+            timestep.advance(frame_time);
+            let alpha = timestep.alpha();
+            assert!(alpha >= 0.0 && alpha < 1.0, "alpha is {}", alpha);
+            frame_time = (frame_time * 1.7 + 0.0031) % 0.2;
+        }
+    }
+}